@@ -0,0 +1,45 @@
+//! Native NN scoring of every 20-bp window of a synthetic genome, the
+//! per-window work of `ScoreModel::WindowDuplex`. Compare the scalar and
+//! vectorised stack sums with `cargo bench --bench window_sums` and
+//! `cargo bench --bench window_sums --features simd`.
+
+use rust_search::thal::{calculate_thermo, create_default_args, stack_sums};
+use std::hint::black_box;
+use std::time::Instant;
+
+const GENOME_LEN: usize = 10_000_000;
+const WINDOW: usize = 20;
+const ROUNDS: u32 = 5;
+
+fn synthetic_genome() -> Vec<u8> {
+    let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+    (0..GENOME_LEN)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            b"ACGT"[(state >> 62) as usize]
+        })
+        .collect()
+}
+
+fn time<F: FnMut() -> f64>(name: &str, mut f: F) {
+    let mut total = 0.0;
+    let t = Instant::now();
+    for _ in 0..ROUNDS {
+        total = black_box(f());
+    }
+    println!("{:<24} {:>10.2?} / round  (sum {:.1})", name, t.elapsed() / ROUNDS, total);
+}
+
+fn main() {
+    let genome = synthetic_genome();
+    let args = create_default_args();
+    let path = if cfg!(feature = "simd") { "simd" } else { "scalar" };
+    time(&format!("stack_sums ({})", path), || {
+        genome.windows(WINDOW).map(|w| stack_sums(w).unwrap().0).sum()
+    });
+    time(&format!("calculate_thermo ({})", path), || {
+        genome.windows(WINDOW).map(|w| calculate_thermo(w, &args).dg).sum()
+    });
+}
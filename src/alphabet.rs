@@ -0,0 +1,93 @@
+/// Byte translation applied to patterns and genome before scoring, letting
+/// non-standard symbols (e.g. inosine `I`) borrow the NN behavior of a
+/// canonical base. Case is preserved so soft-masking survives translation.
+#[derive(Debug, Clone)]
+pub struct Alphabet {
+    table: [u8; 256],
+    custom: bool,
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        let mut table = [0u8; 256];
+        for (i, b) in table.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        Alphabet { table, custom: false }
+    }
+}
+
+impl Alphabet {
+    /// Parse a comma-separated list of `SYMBOL=BASE` pairs, e.g. `I=G,X=A`.
+    /// `BASE` must be one of ACGT; `SYMBOL` must be a letter other than ACGT.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut alphabet = Alphabet::default();
+        for pair in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (from, to) = match pair.as_bytes() {
+                [from, b'=', to] => (from.to_ascii_uppercase(), to.to_ascii_uppercase()),
+                _ => return Err(format!("invalid alphabet entry '{}', expected SYMBOL=BASE", pair)),
+            };
+            if !b"ACGT".contains(&to) {
+                return Err(format!("'{}' must map to one of A, C, G, T", pair));
+            }
+            if !from.is_ascii_alphabetic() || b"ACGT".contains(&from) {
+                return Err(format!("'{}' cannot remap a canonical or non-letter symbol", pair));
+            }
+            alphabet.table[from as usize] = to;
+            alphabet.table[from.to_ascii_lowercase() as usize] = to.to_ascii_lowercase();
+            alphabet.custom = true;
+        }
+        Ok(alphabet)
+    }
+
+    /// Also read RNA `U` as `T`, so pasted RNA is scored with the DNA
+    /// nearest-neighbor parameters rather than as an unknown base.
+    pub fn map_rna_to_dna(&mut self) {
+        self.table[b'U' as usize] = b'T';
+        self.table[b'u' as usize] = b't';
+        self.custom = true;
+    }
+
+    /// True when no symbols have been remapped.
+    pub fn is_identity(&self) -> bool {
+        !self.custom
+    }
+
+    pub fn translate_in_place(&self, seq: &mut [u8]) {
+        if self.custom {
+            for b in seq.iter_mut() {
+                *b = self.table[*b as usize];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_preserves_case() {
+        let alphabet = Alphabet::parse("I=G, x=a").unwrap();
+        let mut seq = b"ACIiTXn".to_vec();
+        alphabet.translate_in_place(&mut seq);
+        assert_eq!(seq, b"ACGgTAn");
+    }
+
+    #[test]
+    fn test_map_rna_to_dna_keeps_other_entries() {
+        let mut alphabet = Alphabet::parse("I=G").unwrap();
+        alphabet.map_rna_to_dna();
+        let mut seq = b"AUGCIu".to_vec();
+        alphabet.translate_in_place(&mut seq);
+        assert_eq!(seq, b"ATGCGt");
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_entries() {
+        assert!(Alphabet::parse("I=N").is_err());
+        assert!(Alphabet::parse("A=G").is_err());
+        assert!(Alphabet::parse("IG").is_err());
+        assert!(Alphabet::parse("").unwrap().is_identity());
+    }
+}
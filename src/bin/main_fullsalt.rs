@@ -1,131 +1,1472 @@
-use clap::Parser;
-use needletail::{parse_fastx_file, Sequence};
-use rayon::prelude::*;
-use aho_corasick::AhoCorasick;
-use std::io;
-use std::collections::{HashMap, HashSet};
+use anstyle::AnsiColor;
+use clap::{ColorChoice, Parser, Subcommand, ValueEnum};
+use needletail::FastxReader;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::process::ExitCode;
+use std::time::Instant;
+use rust_search::alphabet::Alphabet;
+use rust_search::conditions::Conditions;
+use rust_search::exit::{self, Outcome};
+use rust_search::faidx::{Faidx, Locus};
+use rust_search::fastx::{self, PatternFormat};
+use rust_search::regions::{Regions, SeqFilter};
+use rust_search::search::{self, DgHistogram, Funnel, GenomeAmbiguity, Hit, Patterns, ScanOptions, ScoreModel, Strand};
 use rust_search::{
-    thal, ThalArgs, ThalAlignmentType, ThalMode, ABSOLUTE_ZERO,
+    thal, SaltMethod, Temperature, ThalArgs, ThalAlignmentType, ThalMode, CAL_PER_KCAL, KELVIN_OFFSET,
 };
 
+/// primer3 thermodynamic tables used by `thal` for every scanned duplex.
+const PRIMER3_CONFIG: &str = "primer3/src/primer3_config/";
+
+/// Width (kcal/mol) of the --color bands below --threshold: hits at least
+/// one band more stable are yellow, at least two red.
+const COLOR_BAND: f64 = 5.0;
+
+/// Percentiles of window dG printed by --survey.
+const SURVEY_PERCENTILES: [f64; 9] = [1.0, 5.0, 10.0, 25.0, 50.0, 75.0, 90.0, 95.0, 99.0];
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum Format { Tsv, Csv, Boulder, Msgpack }
+
+/// Order of hit lines in the output.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum GroupBy {
+    /// As scanned: record by record, by position
+    Position,
+    /// All hits of each primer together, primers in input order (buffers
+    /// every hit until the scan ends)
+    Pattern,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print the effective thermodynamic parameters and conditions, then exit
+    Params,
+    /// Score the heterodimer of two oligos with thal and exit; each argument
+    /// is a sequence or a FASTA file whose first record is used
+    Dimer { seq1: String, seq2: String },
+    /// Print the minimum free energy and dot-bracket structure of an oligo
+    /// folded on itself (native NN model), then exit; the argument is a
+    /// sequence or a FASTA file whose first record is used
+    Fold { seq: String },
+    /// Tm shift of a primer's perfect duplex from the global conditions to
+    /// a second set (primer3 defaults unless overridden), then exit
+    DeltaTm {
+        seq: String,
+        /// Na+ (mM) of the second condition set
+        #[arg(long, default_value_t = 50.0)] vs_na: f64,
+        /// Mg2+ (mM) of the second condition set
+        #[arg(long, default_value_t = 1.5)] vs_mg: f64,
+        /// dNTPs (mM) of the second condition set
+        #[arg(long, default_value_t = 0.6)] vs_dntp: f64,
+        /// Primer concentration (nM) of the second condition set
+        #[arg(long, default_value_t = 50.0)] vs_dnac: f64,
+    },
+    /// Tabulate the native NN perfect-duplex thermodynamics (id, length, gc,
+    /// dg, dh, ds, tm) of every record in a FASTA of oligos, then exit
+    Score { fasta: String },
+    /// Pass or fail every record in a FASTA of primers against common
+    /// design rules, one column per rule, then exit; exits 1 when any
+    /// primer fails a rule
+    Check {
+        fasta: String,
+        #[command(flatten)] rules: CheckRules,
+    },
+    /// Check the library against published reference values and exit
+    /// non-zero if any deviate beyond tolerance
+    Selftest,
+}
+
+/// Limits applied by the `check` subcommand. Tm is the native NN
+/// perfect-duplex Tm; hairpin and self-dimer dG come from thal.
+#[derive(clap::Args, Debug)]
+struct CheckRules {
+    /// Shortest passing primer (nt)
+    #[arg(long, default_value_t = 18)] min_len: usize,
+    /// Longest passing primer (nt)
+    #[arg(long, default_value_t = 30)] max_len: usize,
+    /// Lowest passing GC fraction
+    #[arg(long, default_value_t = 0.4)] min_gc: f64,
+    /// Highest passing GC fraction
+    #[arg(long, default_value_t = 0.6)] max_gc: f64,
+    /// Lowest passing Tm (C)
+    #[arg(long, default_value_t = 52.0)] min_tm: f64,
+    /// Highest passing Tm (C)
+    #[arg(long, default_value_t = 65.0)] max_tm: f64,
+    /// Longest passing single-base run
+    #[arg(long, default_value_t = 4)] max_homopolymer: usize,
+    /// 3' bases searched for the GC clamp
+    #[arg(long, default_value_t = 5)] clamp_window: usize,
+    /// Fewest G/C in the clamp window that count as a clamp
+    #[arg(long, default_value_t = 1)] min_clamp: usize,
+    /// Most stable passing hairpin dG (kcal/mol)
+    #[arg(long, default_value_t = -2.0)] min_hairpin_dg: f64,
+    /// Most stable passing self-dimer dG (kcal/mol)
+    #[arg(long, default_value_t = -6.0)] min_dimer_dg: f64,
+}
+
+/// Conditions shared across runs can be set once in the environment:
+/// a flag given on the command line wins over its `RUST_SEARCH_*`
+/// variable, which wins over the built-in default.
+///
+/// Exits 0 when hits are reported, 1 when the scan finds none, 2 on a
+/// usage or input error and 3 on an internal error. Ctrl-C stops the scan
+/// at its next chunk, writes the hits found so far and the summary, and
+/// exits 130.
+///
+/// Output is flushed as each record finishes, with that record's hits in
+/// position order, so a pipe sees whole records as the scan goes;
+/// --group-by pattern instead holds every hit until the end.
 #[derive(Parser, Debug)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long)] file: String,
-    #[arg(short, long)] patterns: String,
+    #[command(subcommand)] command: Option<Command>,
+    #[arg(short, long, required = true)] file: Option<String>,
+    #[arg(short, long, required_unless_present = "pattern")] patterns: Option<String>,
+    /// Layout of the --patterns file: FASTA records, or txt with one
+    /// sequence per line (named pattern_1, pattern_2, ...)
+    #[arg(long, value_enum, default_value_t = PatternFormat::Fasta)] pattern_format: PatternFormat,
+    /// Primer sequence given inline; repeatable, and combined with --patterns.
+    /// Named pattern_N, numbered on from any txt --patterns lines
+    #[arg(long, value_name = "SEQ")] pattern: Vec<String>,
     /// Max Delta G threshold (kcal/mol)
-    #[arg(short, long, default_value_t = -10.0)] threshold: f64,
+    #[arg(short, long, env = "RUST_SEARCH_THRESHOLD", default_value_t = -10.0)] threshold: f64,
+    /// Min Delta G (kcal/mol); hits are reported when min-dg <= dG <= threshold
+    #[arg(long, default_value_t = f64::NEG_INFINITY)] min_dg: f64,
+    /// Skip windows whose fraction of non-ACGT bases (e.g. N gaps) exceeds this
+    #[arg(long, default_value_t = 0.0)] max_ambiguous: f64,
+    /// Handling of IUPAC codes in windows kept by --max-ambiguous (raise
+    /// --max-ambiguous for resolve-best or treat-as-mismatch to see any)
+    #[arg(long, value_enum, default_value_t = GenomeAmbiguity::Skip)] genome_ambiguity: GenomeAmbiguity,
     /// Monovalent salt Na+ (mM) - Primer3 default 50.0
-    #[arg(long, default_value_t = 50.0)] na: f64,
+    #[arg(long, env = "RUST_SEARCH_NA", default_value_t = 50.0)] na: f64,
     /// Divalent salt Mg2+ (mM) - Primer3 default 1.5
-    #[arg(long, default_value_t = 1.5)] mg: f64,
+    #[arg(long, env = "RUST_SEARCH_MG", default_value_t = 1.5)] mg: f64,
     /// dNTPs (mM) - Primer3 default 0.6
-    #[arg(long, default_value_t = 0.6)] dntp: f64,
+    #[arg(long, env = "RUST_SEARCH_DNTP", default_value_t = 0.6)] dntp: f64,
     /// Primer concentration (nM) - Primer3 default 50.0
-    #[arg(long, default_value_t = 50.0)] dnac: f64,
+    #[arg(long, env = "RUST_SEARCH_DNAC", default_value_t = 50.0)] dnac: f64,
     /// Temperature (C) for Delta G - default 37.0
-    #[arg(long, default_value_t = 37.0)] temp: f64,
+    #[arg(long, env = "RUST_SEARCH_TEMP", default_value_t = 37.0)] temp: f64,
+    /// Salt correction for the native NN model (--nn-tm, delta-tm, params);
+    /// thal scan duplexes always use primer3's own correction
+    #[arg(long, value_enum, env = "RUST_SEARCH_SALT_METHOD", default_value_t = SaltMethod::Santalucia)] salt_method: SaltMethod,
+    /// Score every native NN duplex as a heterodimer (C_T/4, no symmetry ΔS),
+    /// palindromes included, to match tools that ignore self-complementarity
+    #[arg(long)] no_self_complementary_correction: bool,
     /// Maximum loop size (bp) - Primer3 default 30
-    #[arg(long, default_value_t = 30)] max_loop: i32,
+    #[arg(long, env = "RUST_SEARCH_MAX_LOOP", default_value_t = 30)] max_loop: i32,
+    /// Per-pattern conditions: TSV with an `id` column and any of mv, dv,
+    /// dntp, dnac, temp; patterns not listed use the global values
+    #[arg(long)] conditions: Option<String>,
+    /// Only scan these BED intervals (0-based, half-open); records not listed are skipped
+    #[arg(long)] regions: Option<String>,
+    /// Only scan this locus, CHR:START-END (1-based, inclusive) or a whole
+    /// CHR, read by seeking through the FASTA index; repeatable. Positions
+    /// are still reported in record coordinates; --context stops at the locus
+    #[arg(long, value_name = "LOCUS", conflicts_with_all = ["regions", "circular", "output_template", "mmap"])]
+    region: Vec<String>,
+    /// samtools faidx index of --file used by --region (default: FILE.fai);
+    /// the FASTA must be uncompressed
+    #[arg(long, requires = "region")] fasta_index: Option<String>,
+    /// Only scan records with these ids: comma-separated, or a file of one
+    /// id per line. An id matches the whole header or its first word
+    #[arg(long, value_name = "IDS")] include_seqs: Option<String>,
+    /// Skip records with these ids (same forms as --include-seqs)
+    #[arg(long, value_name = "IDS")] exclude_seqs: Option<String>,
+    /// Suppress a hit starting fewer than N bases after the last reported hit
+    /// of the same motif and strand (greedy left-to-right collapse; 0 = off)
+    #[arg(long, default_value_t = 0)] min_gap: usize,
+    /// Report forward and reverse hits of one primer at the same position
+    /// as one line: the more stable hit, with strand and opposite_dg columns
+    #[arg(long, group = "strand_merge")] merge_strands: bool,
+    /// Worst-case binding per site: score each window against the primer
+    /// and its reverse complement and report only the more stable, with
+    /// strand, fwd_dg and rev_dg columns
+    #[arg(long, group = "strand_merge")] best_strand: bool,
+    /// With --merge-strands, pair hits by canonical motif (the smaller of
+    /// it and its reverse complement) instead of by primer, so primers
+    /// given in both orientations also collapse
+    #[arg(long, requires = "merge_strands")] canonical: bool,
+    /// Skip primers whose 3' end ΔG (kcal/mol) is more negative than this
+    #[arg(long)] min_end_dg: Option<f64>,
+    /// Number of 3' bases examined by --min-end-dg and --require-gc-clamp - Primer3 uses 5
+    #[arg(long, default_value_t = 5)] end_len: usize,
+    /// Append the GC fraction of the matched window as a gc column
+    #[arg(long)] gc: bool,
+    /// Append dG divided by (motif length - 1) as a dg_per_bp column
+    #[arg(long)] normalized: bool,
+    /// Append the native NN Tm of the primer aligned base for base to the
+    /// window, with mismatches scored, as an nn_tm column
+    #[arg(long)] nn_tm: bool,
+    /// Append native NN ΔG columns contrasting the site alone with the
+    /// primer on it: window_dg (the window's perfect duplex) and primer_dg
+    /// (the primer aligned to the window, mismatches scored)
+    #[arg(long)] compare_dg: bool,
+    /// Append the primer's hairpin ΔG (kcal/mol, 0 when it does not fold)
+    /// and an adjusted_dg of dG minus it, so self-folding primers rank
+    /// lower; --threshold still applies to dG
+    #[arg(long)] subtract_hairpin: bool,
+    /// Append the ΔG (kcal/mol) of the primer's 5' and 3' halves, each
+    /// scored as its own duplex, as dg_5prime and dg_3prime columns
+    #[arg(long)] half_dg: bool,
+    /// Append the primer's Wallace rule Tm (2(A+T)+4(G+C)) as an extra column
+    #[arg(long)] wallace: bool,
+    /// Append the reverse complement of the matched genomic window (template strand)
+    #[arg(long)] rc_window: bool,
+    /// Append the matched window with N flanking bases on each side (clamped
+    /// at record ends); flanks are lowercase, the window uppercase
+    #[arg(long)] context: Option<usize>,
+    /// For manual triage, write each hit line's alignment to stderr, or to
+    /// FILE when given: a "# seq_id pos strand dG" line, then the motif, `|`
+    /// under each base the window matches, and the window, in + strand order
+    #[arg(long, value_name = "FILE", num_args = 0..=1)] align_view: Option<Option<PathBuf>>,
+    /// Drop hits whose matched window has no G/C in the --end-len bases
+    /// bound by the primer's 3' end (the window's last bases for + strand
+    /// hits, its first for - strand hits)
+    #[arg(long)] require_gc_clamp: bool,
+    /// Skip primers containing a homopolymer run longer than N bases
+    #[arg(long)] max_run: Option<usize>,
+    /// Also merge a primer into an earlier one that is its reverse
+    /// complement (identical primers are always merged)
+    #[arg(long)] dedup_rc: bool,
+    /// Treat each record as circular so sites spanning the origin are found
+    /// (positions are reported modulo the record length; not applied to --regions)
+    #[arg(long)] circular: bool,
+    /// Treat extra symbols as canonical bases for scoring, e.g. "I=G,X=A"
+    #[arg(long, value_parser = Alphabet::parse, default_value = "")] alphabet: Alphabet,
+    /// Read U as T in patterns and genome, scoring RNA input with DNA
+    /// parameters (a warning is printed; there is no RNA model)
+    #[arg(long)] rna_as_dna: bool,
+    /// Report positions 1-based: the pos column of hit lines and --amplicon
+    /// start (whose end then reads as the inclusive last base). Positions
+    /// are 0-based otherwise, and always in --bedgraph output (BED is
+    /// 0-based half-open), --trace-seeds and msgpack records
+    #[arg(long)] one_based: bool,
+    /// Decimal places for the dG, Tm, dH, dS and Wallace Tm columns
+    #[arg(long, default_value_t = 2)] precision: usize,
+    /// Color the dG column of tsv hit lines on stdout by stability: auto
+    /// colors only when stdout is a terminal and NO_COLOR is unset
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto)] color: ColorChoice,
+    /// Output format: tsv (no header), csv (quoted as needed, with header),
+    /// boulder (primer3 boulder-IO KEY=value records, one per hit) or msgpack
+    /// (a binary stream of hit records, read back with
+    /// search::read_msgpack_hits; extra text columns are not included)
+    #[arg(long, value_enum, default_value_t = Format::Tsv)] format: Format,
+    /// Write each record's hits to its own file, e.g. "out/{seqid}.tsv"
+    /// (parent directories are created)
+    #[arg(long)] output_template: Option<String>,
+    /// Print only the number of reported hits per record, primer and strand
+    /// (seq_id, motif, strand, count) instead of one line per hit
+    #[arg(long, conflicts_with = "output_template")] count_only: bool,
+    /// In-silico PCR: report products between the primers with these ids
+    /// instead of hits: the forward primer on the + strand, the reverse
+    /// primer's reverse complement downstream, the sites not overlapping
+    #[arg(long, num_args = 2, value_names = ["FWD", "REV"],
+        conflicts_with_all = ["count_only", "bedgraph", "output_template", "group_by"])]
+    amplicon: Vec<String>,
+    /// Longest --amplicon product (bp, primers included)
+    #[arg(long, default_value_t = 3000, requires = "amplicon")] max_product_size: usize,
+    /// Most --amplicon products reported per record; repeated sites pair
+    /// combinatorially
+    #[arg(long, default_value_t = 1000, requires = "amplicon")] max_amplicons: usize,
+    /// Warn when the --amplicon primers' own Tms differ by more than this
+    /// (C), and drop products whose site Tms (fwd_tm, rev_tm) do
+    #[arg(long, requires = "amplicon")] max_tm_diff: Option<f64>,
+    /// Output order of hit lines
+    #[arg(long, value_enum, default_value_t = GroupBy::Position, conflicts_with_all = ["output_template", "count_only"])]
+    group_by: GroupBy,
+    /// With --group-by pattern, order each primer's hits by dG, most stable first
+    #[arg(long, requires = "group_by")] sort_dg: bool,
+    /// Mispriming map: print a bedGraph of the most stable ΔG (kcal/mol) any
+    /// primer reaches in a window starting at each position; positions with
+    /// no window under --threshold are omitted
+    #[arg(long, conflicts_with_all = ["output_template", "count_only", "format"])] bedgraph: bool,
+    /// Threshold calibration: score windows as usual but report no hits;
+    /// print percentiles of the dG of every scored window instead
+    #[arg(long, conflicts_with_all = ["count_only", "bedgraph", "amplicon", "output_template", "group_by",
+        "max_hits", "report_no_hits"])]
+    survey: bool,
+    /// Seed length tuning: count the exact 7 bp seed matches of
+    /// each primer, both strands, across the scanned sequence without
+    /// extending or scoring any, and print them per primer instead of hits
+    #[arg(long, conflicts_with_all = ["count_only", "bedgraph", "amplicon", "output_template", "group_by",
+        "max_hits", "report_no_hits", "survey"])]
+    seed_stats: bool,
+    /// Debug aid: append the index of the chunk that produced each hit and
+    /// the hit's offset within it (chunks are per scanned interval)
+    #[arg(long)] debug_chunks: bool,
+    /// List primers with no reported hit ("no hits", id, sequence) on
+    /// stderr, or in FILE when given
+    #[arg(long, value_name = "FILE", num_args = 0..=1)] report_no_hits: Option<Option<PathBuf>>,
+    /// Debug aid: write every seed match tried against each motif to this
+    /// TSV with the extension's outcome (passed, failed-dg, clipped-start,
+    /// clipped-end, other-chunk, duplicate, ambiguous, no-gc-clamp)
+    #[arg(long)] trace_seeds: Option<PathBuf>,
+    /// Bases per parallel work unit
+    #[arg(long, default_value_t = search::CHUNK_SIZE)] chunk_size: usize,
+    /// Bases shared by adjacent chunks (default: the longest pattern
+    /// length); a shorter value is raised to the longest pattern minus one
+    #[arg(long)] overlap: Option<usize>,
+    /// Memory-map the genome file instead of reading it through a buffer
+    #[arg(long)] mmap: bool,
+    /// Stop once N hits have been reported. These are the first found by the
+    /// parallel scan, not the N best by dG nor necessarily the N leftmost;
+    /// fewer may be reported if --min-gap collapses some
+    #[arg(long)] max_hits: Option<usize>,
+    /// Warn about malformed FASTA/FASTQ records and keep going instead of
+    /// failing (the parser may be unable to resume past some errors)
+    #[arg(long)] skip_errors: bool,
+    /// Write the run's inputs, parameters, counts and timing to this file
+    /// as one JSON object when the scan finishes
+    #[arg(long)] summary_json: Option<PathBuf>,
 }
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
+/// Provenance record written by --summary-json.
+#[derive(Serialize)]
+struct RunSummary<'a> {
+    version: &'static str,
+    genome: &'a str,
+    patterns_file: Option<&'a str>,
+    inline_patterns: &'a [String],
+    parameters: SummaryParameters,
+    patterns_loaded: usize,
+    patterns_skipped: usize,
+    records: usize,
+    seed_hits: usize,
+    full_length_windows: usize,
+    passing_windows: usize,
+    hits_reported: usize,
+    elapsed_seconds: f64,
+}
 
-    // Initialize thermodynamic parameters from primer3_config
-    rust_search::thal::ensure_parameters_loaded("primer3/src/primer3_config/")
-        .expect("Failed to load thermodynamic parameters");
+/// The scan settings that decide which hits are reported.
+#[derive(Serialize)]
+struct SummaryParameters {
+    threshold: f64,
+    /// None when unbounded (JSON has no infinity).
+    min_dg: Option<f64>,
+    na_mm: f64,
+    mg_mm: f64,
+    dntp_mm: f64,
+    dnac_nm: f64,
+    temp_c: f64,
+    max_loop: i32,
+    max_ambiguous: f64,
+    chunk_size: usize,
+    overlap: usize,
+    regions: Option<String>,
+    conditions: Option<String>,
+    circular: bool,
+}
 
-    let mut pattern_reader = parse_fastx_file(&args.patterns).expect("Invalid pattern file");
-    let mut all_motifs = Vec::new();
-    
-    // Mapping from unique seed to list of (motif_idx, offset)
-    let mut seed_map: HashMap<Vec<u8>, Vec<(usize, usize)>> = HashMap::new();
-
-    while let Some(record) = pattern_reader.next() {
-        let rec = record.unwrap();
-        let seq = rec.seq().to_ascii_uppercase();
-        let rc = rec.reverse_complement().to_ascii_uppercase();
-        for s in vec![seq, rc] {
-            let motif_idx = all_motifs.len();
-            all_motifs.push(s.clone());
-            
-            if s.len() >= 7 {
-                for offset in 0..=(s.len() - 7) {
-                    let seed = s[offset..offset+7].to_vec();
-                    seed_map.entry(seed).or_default().push((motif_idx, offset));
+/// Destination for hit rows in the selected `--format`.
+enum Sink {
+    Tsv(BufWriter<Box<dyn Write>>),
+    Csv(Box<csv::Writer<Box<dyn Write>>>),
+    /// Keys derived from the header, in column order.
+    Boulder(BufWriter<Box<dyn Write>>, Vec<String>),
+    /// Hits themselves rather than rows; see `write_hit`.
+    Msgpack(BufWriter<Box<dyn Write>>),
+}
+
+/// Boulder-IO key for an output column: `SEQUENCE_ID` for the record id,
+/// otherwise the column name as a `PRIMER_` tag (e.g. `PRIMER_TM`).
+fn boulder_key(column: &str) -> String {
+    match column {
+        "seq_id" => "SEQUENCE_ID".to_string(),
+        "motif" => "PRIMER_SEQUENCE".to_string(),
+        _ => format!("PRIMER_{}", column.to_ascii_uppercase()),
+    }
+}
+
+impl Sink {
+    fn new(format: Format, header: &[&str], out: Box<dyn Write>) -> io::Result<Self> {
+        Ok(match format {
+            Format::Tsv => Sink::Tsv(BufWriter::new(out)),
+            Format::Csv => {
+                let mut w = csv::Writer::from_writer(out);
+                w.write_record(header)?;
+                Sink::Csv(Box::new(w))
+            }
+            Format::Boulder => {
+                Sink::Boulder(BufWriter::new(out), header.iter().map(|c| boulder_key(c)).collect())
+            }
+            Format::Msgpack => Sink::Msgpack(BufWriter::new(out)),
+        })
+    }
+
+    fn write_row(&mut self, fields: &[String]) -> io::Result<()> {
+        match self {
+            Sink::Tsv(w) => writeln!(w, "{}", fields.join("\t")),
+            Sink::Csv(w) => Ok(w.write_record(fields)?),
+            Sink::Boulder(w, keys) => {
+                for (key, value) in keys.iter().zip(fields) {
+                    writeln!(w, "{}={}", key, value)?;
                 }
+                writeln!(w, "=")
             }
+            Sink::Msgpack(_) => Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "--format msgpack only writes hit records")),
         }
     }
 
-    let mut unique_seeds = Vec::new();
-    let mut seed_to_motifs = Vec::new();
-    for (seed, motifs) in seed_map {
-        unique_seeds.push(seed);
-        seed_to_motifs.push(motifs);
+    /// A hit line: `row` as rendered for the text formats, or `hit` itself
+    /// for msgpack.
+    fn write_hit(&mut self, hit: &Hit, row: &[String]) -> io::Result<()> {
+        match self {
+            Sink::Msgpack(w) => search::write_msgpack_hit(w, hit),
+            _ => self.write_row(row),
+        }
     }
 
-    let ac = AhoCorasick::new(&unique_seeds).unwrap();
-    let mut reader = parse_fastx_file(&args.file).expect("Genome file error");
-    let chunk_size = 1_000_000;
-    let overlap = 100;
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Tsv(w) => w.flush(),
+            Sink::Csv(w) => w.flush(),
+            Sink::Boulder(w, _) => w.flush(),
+            Sink::Msgpack(w) => w.flush(),
+        }
+    }
+}
 
-    // Create thal_args for thermodynamic calculations
-    let thal_args = ThalArgs {
+/// `text` (a formatted dG) wrapped in the ANSI color of its --color band,
+/// or unchanged when it is within one band of `threshold`.
+fn color_dg(text: &str, dg: f64, threshold: f64) -> String {
+    let style = if dg <= threshold - 2.0 * COLOR_BAND {
+        AnsiColor::Red.on_default()
+    } else if dg <= threshold - COLOR_BAND {
+        AnsiColor::Yellow.on_default()
+    } else {
+        return text.to_string();
+    };
+    format!("{}{}{:#}", style, text, style)
+}
+
+/// Expand `{seqid}` in an --output-template (path separators in the id are
+/// replaced) and create the file's parent directories.
+fn record_output_path(template: &str, seq_id: &str) -> io::Result<PathBuf> {
+    let safe_id = seq_id.replace(['/', '\\'], "_");
+    let path = PathBuf::from(template.replace("{seqid}", &safe_id));
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
+/// A `dimer` operand: the first record of `arg` if it names a file,
+/// otherwise `arg` itself as a sequence. Uppercased.
+fn read_oligo(arg: &str) -> io::Result<Vec<u8>> {
+    if !std::path::Path::new(arg).is_file() {
+        return Ok(arg.trim().to_ascii_uppercase().into_bytes());
+    }
+    let mut reader = fastx::open(arg)?;
+    match reader.next() {
+        Some(Ok(rec)) => Ok(fastx::normalize_seq(rec.seq()).into_owned()),
+        Some(Err(e)) => Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} has no records", arg))),
+    }
+}
+
+/// A published value reproduced by `selftest`.
+struct Reference {
+    name: &'static str,
+    expected: f64,
+    tolerance: f64,
+    observed: fn() -> f64,
+}
+
+/// 1 M Na+, no Mg2+/dNTP: the standard state of the SantaLucia 1998 tables.
+fn one_molar_args() -> ThalArgs {
+    ThalArgs { mv: 1000.0, dv: 0.0, dntp: 0.0, ..thal::create_default_args() }
+}
+
+const REFERENCES: &[Reference] = &[
+    Reference {
+        // Worked example in SantaLucia (1998) PNAS 95:1460
+        name: "SantaLucia 1998 CGTTGA dG37 (kcal/mol)",
+        expected: -5.35,
+        tolerance: 0.1,
+        observed: || thal::calculate_thermo(b"CGTTGA", &one_molar_args()).dg / CAL_PER_KCAL,
+    },
+    Reference {
+        // Sum of the tabulated dG37 stacks, initiation and symmetry terms
+        name: "SantaLucia 1998 CGATCG dG37, self-complementary (kcal/mol)",
+        expected: -5.43,
+        tolerance: 0.1,
+        observed: || thal::calculate_thermo(b"CGATCG", &one_molar_args()).dg / CAL_PER_KCAL,
+    },
+    Reference {
+        name: "SantaLucia 1998 CGTTGA dH (kcal/mol)",
+        expected: -41.2,
+        tolerance: 0.01,
+        observed: || thal::calculate_thermo(b"CGTTGA", &one_molar_args()).dh / CAL_PER_KCAL,
+    },
+    Reference {
+        // primer3.calc_heterodimer with primer3-py defaults
+        name: "primer3-py heterodimer ATCGATCGATCGATCGATCG Tm (C)",
+        expected: 57.29,
+        tolerance: 0.5,
+        observed: || {
+            let args = ThalArgs { dv: 1.5, dntp: 0.6, ..thal::create_default_args() };
+            thal::thal(b"ATCGATCGATCGATCGATCG", b"CGATCGATCGATCGATCGAT", &args, ThalMode::Fast).temp
+        },
+    },
+    Reference {
+        name: "primer3-py heterodimer ATCGATCGATCGATCGATCG dG (kcal/mol)",
+        expected: -21.07,
+        tolerance: 0.1,
+        observed: || {
+            let args = ThalArgs { dv: 1.5, dntp: 0.6, ..thal::create_default_args() };
+            thal::thal(b"ATCGATCGATCGATCGATCG", b"CGATCGATCGATCGATCGAT", &args, ThalMode::Fast).dg / CAL_PER_KCAL
+        },
+    },
+];
+
+/// `selftest` subcommand: print each reference check with its deviation
+/// and fail if any is out of tolerance.
+fn run_selftest() -> io::Result<()> {
+    let mut failed = 0;
+    println!("status\tcheck\texpected\tobserved\tdeviation");
+    for r in REFERENCES {
+        let observed = (r.observed)();
+        let deviation = observed - r.expected;
+        let pass = deviation.abs() <= r.tolerance;
+        if !pass {
+            failed += 1;
+        }
+        println!("{}\t{}\t{:.2}\t{:.2}\t{:+.3}", if pass { "PASS" } else { "FAIL" },
+            r.name, r.expected, observed, deviation);
+    }
+    if failed > 0 {
+        return Err(io::Error::other(format!("{} of {} self-test checks failed", failed, REFERENCES.len())));
+    }
+    Ok(())
+}
+
+/// `score` subcommand: one table row per oligo in `path`. Records with
+/// bases the native model cannot score are skipped with a warning.
+fn run_score(path: &str, args: &Args, thal_args: &ThalArgs) -> io::Result<()> {
+    let mut reader = fastx::open(path)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    writeln!(out, "id\tlength\tgc\tdg\tdh\tds\ttm")?;
+    let p = args.precision;
+    while let Some(record) = reader.next() {
+        let Some(rec) = fastx::check_record(record, path, args.skip_errors)? else { continue };
+        let id = String::from_utf8_lossy(rec.id()).into_owned();
+        let mut seq = fastx::normalize_seq(rec.seq()).into_owned();
+        args.alphabet.translate_in_place(&mut seq);
+        let result = thal::calculate_thermo(&seq, thal_args);
+        if !result.msg.is_empty() {
+            eprintln!("Skipping {}: {}", id, result.msg);
+            continue;
+        }
+        writeln!(out, "{}\t{}\t{:.*}\t{:.*}\t{:.*}\t{:.*}\t{:.*}", id, seq.len(), p, thal::gc_fraction(&seq),
+            p, result.dg / CAL_PER_KCAL, p, result.dh / CAL_PER_KCAL, p, result.ds, p, result.temp)?;
+    }
+    out.flush()
+}
+
+/// `check` subcommand: one row of PASS/FAIL per primer in `path`, and
+/// whether every primer passed every rule. Records with bases the native
+/// model cannot score are skipped with a warning.
+fn run_check(path: &str, rules: &CheckRules, args: &Args, thal_args: &ThalArgs) -> io::Result<bool> {
+    let mut reader = fastx::open(path)?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    writeln!(out, "id\tlength\tgc\ttm\thomopolymer\tgc_clamp\thairpin\tself_dimer\tresult")?;
+    let verdict = |pass: bool| if pass { "PASS" } else { "FAIL" };
+    let mut all_pass = true;
+    while let Some(record) = reader.next() {
+        let Some(rec) = fastx::check_record(record, path, args.skip_errors)? else { continue };
+        let id = String::from_utf8_lossy(rec.id()).into_owned();
+        let mut seq = fastx::normalize_seq(rec.seq()).into_owned();
+        args.alphabet.translate_in_place(&mut seq);
+        let duplex = thal::calculate_thermo(&seq, thal_args);
+        if !duplex.msg.is_empty() {
+            eprintln!("Skipping {}: {}", id, duplex.msg);
+            continue;
+        }
+        let gc = thal::gc_fraction(&seq);
+        let checks = [
+            (rules.min_len..=rules.max_len).contains(&seq.len()),
+            (rules.min_gc..=rules.max_gc).contains(&gc),
+            (rules.min_tm..=rules.max_tm).contains(&duplex.temp),
+            thal::max_homopolymer_run(&seq) <= rules.max_homopolymer,
+            thal::gc_clamp_count(&seq, rules.clamp_window) >= rules.min_clamp,
+            thal::hairpin(&seq, thal_args).dg / CAL_PER_KCAL >= rules.min_hairpin_dg,
+            thal::thal(&seq, &seq, thal_args, ThalMode::Fast).dg / CAL_PER_KCAL >= rules.min_dimer_dg,
+        ];
+        let pass = checks.iter().all(|&c| c);
+        all_pass &= pass;
+        write!(out, "{}", id)?;
+        for check in checks {
+            write!(out, "\t{}", verdict(check))?;
+        }
+        writeln!(out, "\t{}", verdict(pass))?;
+    }
+    out.flush()?;
+    Ok(all_pass)
+}
+
+/// `params` subcommand: the reaction conditions in effect and the native
+/// nearest-neighbor model, for comparison against primer3-py.
+fn print_params(args: &ThalArgs) {
+    println!("# Conditions");
+    println!("Na+ (mM)\t{:.2}", args.mv);
+    println!("Mg2+ (mM)\t{:.2}", args.dv);
+    println!("dNTP (mM)\t{:.2}", args.dntp);
+    println!("Na+ equivalent (mM)\t{:.2}\tNa + 120*sqrt(max(Mg - dNTP, 0))", thal::na_equivalent(args));
+    println!("DNA (nM)\t{:.2}", args.dna_conc);
+    println!("Temperature (C)\t{:.2}", args.temp.as_celsius());
+    println!("Max loop (bp)\t{}", args.max_loop);
+    println!();
+    println!("# Scan duplexes: primer3 thal with tables from {}", PRIMER3_CONFIG);
+    println!();
+    println!("# Native model: SantaLucia 1998 unified NN (library calculate_thermo)");
+    println!("stack\tdH (kcal/mol)\tdS (cal/K/mol)\tdG37 (kcal/mol)");
+    for [a, b] in thal::NN_STACKS {
+        let (dh, ds) = thal::nn_params(a, b).expect("NN_STACKS are canonical");
+        let dg37 = dh - (37.0 + KELVIN_OFFSET) * ds / CAL_PER_KCAL;
+        println!("{}{}/{}{}\t{:.1}\t{:.1}\t{:.2}", a as char, b as char,
+            thal::complement(a).unwrap() as char, thal::complement(b).unwrap() as char, dh, ds, dg37);
+    }
+    for (label, base) in [("init w/ terminal GC", b'G'), ("init w/ terminal AT", b'A')] {
+        let (dh, ds) = thal::terminal_init(base);
+        println!("{}\t{:.1}\t{:.1}\t{:.2}", label, dh, ds, dh - (37.0 + KELVIN_OFFSET) * ds / CAL_PER_KCAL);
+    }
+    println!("symmetry correction\t0.0\t-1.4\t0.43");
+    println!();
+    println!("# Tm = dH / (dS + R ln(C / x)), R = {}, x = {}", thal::R_CAL,
+        if args.symmetry_correction { "1 if self-complementary else 4" } else { "4 (self-complementarity ignored)" });
+    println!("{}", match args.salt_method {
+        SaltMethod::Santalucia => "# Salt correction: dS += 0.368 * (N - 1) * ln(Na_eq / 1000)   (SantaLucia 1998)",
+        SaltMethod::Schildkraut => "# Salt correction: Tm += 16.6 * log10(Na_eq / 1000)   (Schildkraut 1965)",
+        SaltMethod::Owczarzy => "# Salt correction: 1/Tm += (4.29 fGC - 3.95)e-5 ln(Na) + 9.40e-6 ln(Na)^2, Na = Na_eq / 1000   (Owczarzy 2004)",
+    });
+}
+
+/// Subcommands other than `params`, run once the primer3 tables are loaded.
+fn run_command(command: &Command, args: &Args, thal_args: &ThalArgs) -> io::Result<Outcome> {
+    let p = args.precision;
+    match command {
+        Command::Params => print_params(thal_args),
+        Command::Fold { seq } => {
+            let mut seq = read_oligo(seq)?;
+            args.alphabet.translate_in_place(&mut seq);
+            if seq.len() > thal::MFE_MAX_LEN {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                    "fold takes at most {} bases, got {}", thal::MFE_MAX_LEN, seq.len())));
+            }
+            let (dg, structure) = thal::mfe_fold(&seq, thal_args);
+            println!("dg\t{:.*}", p, dg / CAL_PER_KCAL);
+            println!("structure\t{}", structure);
+        }
+        Command::DeltaTm { seq, vs_na, vs_mg, vs_dntp, vs_dnac } => {
+            let mut seq = read_oligo(seq)?;
+            args.alphabet.translate_in_place(&mut seq);
+            let vs_args = ThalArgs { mv: *vs_na, dv: *vs_mg, dntp: *vs_dntp, dna_conc: *vs_dnac, ..thal_args.clone() };
+            println!("tm\t{:.*}", p, thal::calculate_thermo(&seq, thal_args).temp);
+            println!("vs_tm\t{:.*}", p, thal::calculate_thermo(&seq, &vs_args).temp);
+            println!("delta_tm\t{:.*}", p, thal::delta_tm(&seq, thal_args, &vs_args));
+        }
+        Command::Selftest => run_selftest()?,
+        Command::Score { fasta } => run_score(fasta, args, thal_args)?,
+        Command::Check { fasta, rules } => {
+            let all_pass = run_check(fasta, rules, args, thal_args)?;
+            return Ok(if all_pass { Outcome::Hits } else { Outcome::NoHits });
+        }
+        Command::Dimer { seq1, seq2 } => {
+            let mut seq1 = read_oligo(seq1)?;
+            let mut seq2 = read_oligo(seq2)?;
+            args.alphabet.translate_in_place(&mut seq1);
+            args.alphabet.translate_in_place(&mut seq2);
+            let result = thal::heterodimer(&seq1, &seq2, thal_args)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+            if !result.msg.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, result.msg));
+            }
+            println!("dg\t{:.*}", p, result.dg / CAL_PER_KCAL);
+            println!("dh\t{:.*}", p, result.dh / CAL_PER_KCAL);
+            println!("ds\t{:.*}", p, result.ds);
+            println!("tm\t{:.*}", p, result.temp);
+            println!("align_end_1\t{}", result.align_end_1);
+            println!("align_end_2\t{}", result.align_end_2);
+        }
+    }
+    Ok(Outcome::Hits)
+}
+
+/// Primers as (id, sequence): the --patterns file first, then each inline
+/// --pattern. Sequences without a name (txt lines, then inline patterns)
+/// are numbered pattern_1, pattern_2, ... in that order. An empty
+/// --patterns file loads nothing.
+fn load_primers(args: &Args) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut primers = Vec::new();
+    let mut unnamed = Vec::new();
+    match (args.patterns.as_deref(), args.pattern_format) {
+        (Some(patterns), PatternFormat::Fasta) => {
+            let mut pattern_reader = match fastx::open(patterns) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+                reader => Some(reader?),
+            };
+            while let Some(record) = pattern_reader.as_mut().and_then(|reader| reader.next()) {
+                let Some(rec) = fastx::check_record(record, patterns, args.skip_errors)? else { continue };
+                primers.push((String::from_utf8_lossy(rec.id()).into_owned(), fastx::normalize_seq(rec.seq()).into_owned()));
+            }
+        }
+        (Some(patterns), PatternFormat::Txt) => unnamed.extend(fastx::read_text_patterns(patterns)?),
+        (None, _) => {}
+    }
+    unnamed.extend(args.pattern.iter().map(|pattern| fastx::normalize_seq(pattern.as_bytes().into()).into_owned()));
+    for (i, seq) in unnamed.into_iter().enumerate() {
+        primers.push((format!("pattern_{}", i + 1), seq));
+    }
+    Ok(primers)
+}
+
+/// Column names of the scan's output rows for the options in `args`.
+fn output_header(args: &Args) -> Vec<&'static str> {
+    if args.seed_stats {
+        return vec!["id", "seed_hits"];
+    }
+    if args.survey {
+        return vec!["percentile", "dg"];
+    }
+    if !args.amplicon.is_empty() {
+        return vec!["seq_id", "start", "end", "size", "fwd_id", "fwd_dg", "fwd_tm", "rev_id", "rev_dg", "rev_tm", "tm_diff"];
+    }
+    if args.count_only {
+        return vec!["seq_id", "motif", "strand", "count"];
+    }
+    let mut header = Hit::COLUMNS.to_vec();
+    let extras: [(bool, &[&'static str]); 12] = [
+        (args.wallace, &["wallace_tm"]),
+        (args.gc, &["gc"]),
+        (args.subtract_hairpin, &["hairpin_dg", "adjusted_dg"]),
+        (args.half_dg, &["dg_5prime", "dg_3prime"]),
+        (args.normalized, &["dg_per_bp"]),
+        (args.nn_tm, &["nn_tm"]),
+        (args.compare_dg, &["window_dg", "primer_dg"]),
+        (args.rc_window, &["window_rc"]),
+        (args.context.is_some(), &["context"]),
+        (args.merge_strands, &["strand", "opposite_dg"]),
+        (args.best_strand, &["strand", "fwd_dg", "rev_dg"]),
+        (args.debug_chunks, &["chunk", "chunk_offset"]),
+    ];
+    for (on, columns) in extras {
+        if on {
+            header.extend(columns);
+        }
+    }
+    header
+}
+
+/// Reject output options that cannot be combined.
+fn check_output_flags(args: &Args) -> io::Result<()> {
+    if args.format == Format::Msgpack
+        && (args.count_only || args.survey || args.seed_stats || !args.amplicon.is_empty() || args.group_by == GroupBy::Pattern)
+    {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            "--format msgpack only writes hit records, not --count-only, --survey, --seed-stats, --amplicon or --group-by pattern output"));
+    }
+    if let Some(template) = &args.output_template {
+        if !template.contains("{seqid}") {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                "--output-template must contain {seqid}"));
+        }
+    }
+    Ok(())
+}
+
+/// The global thal conditions for `args`; --conditions overrides them per
+/// primer.
+fn thal_args(args: &Args) -> ThalArgs {
+    ThalArgs {
         alignment_type: ThalAlignmentType::Any,
         max_loop: args.max_loop,
         mv: args.na,
         dv: args.mg,
         dntp: args.dntp,
         dna_conc: args.dnac,
-        temp: args.temp + ABSOLUTE_ZERO,
+        temp: Temperature::celsius(args.temp),
         dimer: 1,
-    };
+        salt_method: args.salt_method,
+        symmetry_correction: !args.no_self_complementary_correction,
+    }
+}
 
-    while let Some(record) = reader.next() {
-        let rec = record.unwrap();
-        let seq_id = String::from_utf8_lossy(rec.id()).to_string();
-        let full_seq = rec.seq();
-
-        (0..full_seq.len()).into_par_iter().step_by(chunk_size - overlap).for_each(|start| {
-            let end = (start + chunk_size).min(full_seq.len());
-            let chunk = full_seq[start..end].to_ascii_uppercase();
-            
-            let mut evaluated = HashSet::new();
-
-            for mat in ac.find_overlapping_iter(&chunk) {
-                let seed_idx = mat.pattern().as_usize();
-                let motifs = &seed_to_motifs[seed_idx];
-                let hit_pos = mat.start();
-
-                for &(motif_idx, offset) in motifs {
-                    let motif = &all_motifs[motif_idx];
-                    let genome_start = hit_pos as isize - offset as isize;
-                    let genome_end = genome_start + motif.len() as isize;
-
-                    if genome_start >= 0 && genome_end <= chunk.len() as isize {
-                        let is_last_chunk = end == full_seq.len();
-                        if is_last_chunk || genome_start < (chunk_size - overlap) as isize {
-                            if evaluated.insert((motif_idx, genome_start)) {
-                                let vicinity = &chunk[genome_start as usize .. genome_end as usize];
-                                // Use the thal function from the library
-                                let result = thal::thal(motif, vicinity, &thal_args, ThalMode::Fast);
-                                
-                                // ΔG is in cal/mol, convert to kcal/mol for threshold comparison
-                                let dg_kcal = result.dg / 1000.0;
-                                
-                                if dg_kcal <= args.threshold {
-                                    println!("{}\t{}\t{:.2}\t{:.2}\t{:.2}\t{:.2}\t{}", 
-                                        seq_id, start + genome_start as usize, dg_kcal, result.temp, 
-                                        result.dh / 1000.0, result.ds,
-                                        String::from_utf8_lossy(motif));
-                                }
-                            }
-                        }
-                    }
+/// The primers a scan searches for, after filtering and merging, with the
+/// per-primer columns computed once up front.
+struct Primers {
+    patterns: Patterns,
+    /// Id of each kept primer, in `patterns` order.
+    ids: Vec<String>,
+    /// Each merged-away id -> the id it was merged into.
+    merged: HashMap<String, String>,
+    /// Primers loaded before filtering and merging.
+    n_loaded: usize,
+    /// Per motif, as the primer reads on that strand (--subtract-hairpin).
+    hairpin_dg: Option<Vec<f64>>,
+    /// Per primer, in the orientation it was given (--half-dg).
+    half_dg: Option<Vec<(f64, f64)>>,
+}
+
+impl Primers {
+    /// Load the primers of `args`, skipping those that fail --min-end-dg or
+    /// --max-run and merging repeated sequences.
+    fn load(args: &Args, conditions: &Conditions, thal_args: &ThalArgs) -> io::Result<Self> {
+        let primers = load_primers(args)?;
+        if primers.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no patterns loaded"));
+        }
+        let n_loaded = primers.len();
+        let mut kept: Vec<(Vec<u8>, ThalArgs)> = Vec::new();
+        let mut ids: Vec<String> = Vec::new();
+        // Sequence (canonical with --dedup-rc) -> index in kept
+        let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut merged = HashMap::new();
+        for (id, seq) in primers {
+            let mut seq = seq.to_ascii_uppercase();
+            args.alphabet.translate_in_place(&mut seq);
+            let primer_args = conditions.args_for(&id, thal_args);
+            if let Some(min_end_dg) = args.min_end_dg {
+                let end_dg = thal::three_prime_stability(&seq, args.end_len, &primer_args) / CAL_PER_KCAL;
+                if end_dg < min_end_dg {
+                    eprintln!("Skipping {}: 3' end ΔG {:.2} kcal/mol is below {}", id, end_dg, min_end_dg);
+                    continue;
+                }
+            }
+            if let Some(max_run) = args.max_run {
+                let run = thal::max_homopolymer_run(&seq);
+                if run > max_run {
+                    eprintln!("Skipping {}: homopolymer run of {} exceeds {}", id, run, max_run);
+                    continue;
                 }
             }
+            // A repeated sequence would report every hit once per copy
+            let key = if args.dedup_rc { thal::canonical_kmer(&seq) } else { seq.clone() };
+            if let Some(&first) = seen.get(&key) {
+                let why = if kept[first].0 == seq { "identical sequence" } else { "reverse complement" };
+                eprintln!("Warning: merging pattern {} into {} ({})", id, ids[first], why);
+                merged.insert(id, ids[first].clone());
+                continue;
+            }
+            seen.insert(key, kept.len());
+            kept.push((seq, primer_args));
+            ids.push(id);
+        }
+        let patterns = Patterns::new(kept);
+        // An unfolded primer (positive ΔG) is not penalized
+        let hairpin_dg = args.subtract_hairpin.then(|| {
+            (0..patterns.len())
+                .map(|motif_idx| {
+                    let primer_args = conditions.args_for(&ids[motif_idx / 2], thal_args);
+                    (thal::hairpin(patterns.motif(motif_idx), &primer_args).dg / CAL_PER_KCAL).min(0.0)
+                })
+                .collect()
+        });
+        // The given orientation, so 3' means the primer's own 3' end
+        let half_dg = args.half_dg.then(|| {
+            (0..patterns.len() / 2)
+                .map(|primer_idx| {
+                    let primer_args = conditions.args_for(&ids[primer_idx], thal_args);
+                    let (five, three) = thal::half_stability(patterns.motif(2 * primer_idx), &primer_args);
+                    (five / CAL_PER_KCAL, three / CAL_PER_KCAL)
+                })
+                .collect()
         });
+        Ok(Primers { patterns, ids, merged, n_loaded, hairpin_dg, half_dg })
     }
-    Ok(())
+
+    /// Primer indices (forward, reverse) of the --amplicon pair, if given.
+    /// Warns when their Tms differ by more than --max-tm-diff.
+    fn amplicon_pair(&self, args: &Args, conditions: &Conditions, thal_args: &ThalArgs) -> io::Result<Option<(usize, usize)>> {
+        let [fwd, rev] = args.amplicon.as_slice() else { return Ok(None) };
+        // A primer merged as a duplicate resolves to the one it joined
+        let index_of = |id: &String| {
+            let loaded = self.merged.get(id).unwrap_or(id);
+            self.ids.iter().position(|k| k == loaded).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("--amplicon primer {} is not loaded", id))
+            })
+        };
+        let (fwd, rev) = (index_of(fwd)?, index_of(rev)?);
+        if let Some(max_tm_diff) = args.max_tm_diff {
+            let primer_tm = |i: usize| {
+                thal::calculate_thermo(self.patterns.motif(2 * i), &conditions.args_for(&self.ids[i], thal_args)).temp
+            };
+            let diff = (primer_tm(fwd) - primer_tm(rev)).abs();
+            if diff > max_tm_diff {
+                eprintln!("Warning: --amplicon primers {} and {} differ in Tm by {:.2} C (--max-tm-diff {})",
+                    self.ids[fwd], self.ids[rev], diff, max_tm_diff);
+            }
+        }
+        Ok(Some((fwd, rev)))
+    }
+}
+
+/// Scan settings for `args`, given the longest motif searched for.
+fn scan_options(args: &Args, max_motif_len: usize) -> io::Result<ScanOptions> {
+    // scan_intervals raises a shorter overlap to fit the longest pattern
+    let overlap = args.overlap.unwrap_or(max_motif_len).max(max_motif_len.saturating_sub(1));
+    if args.chunk_size <= overlap {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+            "--chunk-size {} must exceed the chunk overlap ({})", args.chunk_size, overlap)));
+    }
+    Ok(ScanOptions {
+        // A survey needs only the funnel's histogram, so nothing passes
+        threshold: if args.survey { f64::NEG_INFINITY } else { args.threshold },
+        min_dg: args.min_dg,
+        max_ambiguous: args.max_ambiguous,
+        genome_ambiguity: args.genome_ambiguity,
+        model: ScoreModel::Thal,
+        gc_clamp: args.require_gc_clamp.then_some(args.end_len),
+        nn_tm: args.nn_tm,
+        compare_dg: args.compare_dg,
+        best_strand: args.best_strand,
+        gc: args.gc,
+        stop_after: args.max_hits,
+        cancel: Some(exit::handle_interrupt()?),
+        seeds_only: args.seed_stats,
+        chunk_size: args.chunk_size,
+        chunk_overlap: overlap,
+    })
+}
+
+fn no_records(genome: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("no genome records in {}", genome))
+}
+
+/// A reader over the genome, and each record's start in its full sequence
+/// (empty unless --region reads loci only).
+fn open_genome(args: &Args, genome: &str) -> io::Result<(Box<dyn FastxReader>, Vec<usize>)> {
+    let opened = if !args.region.is_empty() {
+        let index_path = args.fasta_index.clone().unwrap_or_else(|| format!("{}.fai", genome));
+        let index = Faidx::from_path(&index_path)?;
+        let loci = args.region.iter().map(|arg| Locus::parse(arg)).collect::<io::Result<Vec<_>>>()?;
+        fastx::open_loci(genome, &index, &loci)
+    } else if args.mmap {
+        fastx::open_mmap(genome).map(|reader| (reader, Vec::new()))
+    } else {
+        fastx::open(genome).map(|reader| (reader, Vec::new()))
+    };
+    match opened {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(no_records(genome)),
+        opened => opened,
+    }
+}
+
+/// A funnel collecting what --trace-seeds, --survey and --seed-stats need.
+fn scan_funnel(args: &Args, n_motifs: usize) -> Funnel {
+    let mut funnel = if args.trace_seeds.is_some() { Funnel::with_trace() } else { Funnel::default() };
+    if args.survey {
+        funnel.survey = Some(DgHistogram::new());
+    }
+    if args.seed_stats {
+        funnel.seed_counts = Some((0..n_motifs).map(|_| AtomicUsize::new(0)).collect());
+    }
+    funnel
+}
+
+/// Where a scan writes, and what it holds until the scan ends.
+struct Outputs {
+    header: Vec<&'static str>,
+    /// None with --output-template, which opens a sink per record.
+    stdout: Option<Sink>,
+    trace: Option<BufWriter<File>>,
+    align: Option<Box<dyn Write>>,
+    color: bool,
+    /// Per primer (motif pair): whether any hit was reported.
+    primer_hit: Vec<bool>,
+    /// --group-by pattern: (primer, dG, row) held until the scan ends.
+    grouped: Vec<(usize, f64, Vec<String>)>,
+    n_products: usize,
+}
+
+impl Outputs {
+    fn open(args: &Args, n_primers: usize) -> io::Result<Self> {
+        let trace = match &args.trace_seeds {
+            Some(path) => {
+                let mut w = BufWriter::new(File::create(path)?);
+                writeln!(w, "seq_id\tseed_pos\tmotif\tstrand\toffset\tchunk\toutcome")?;
+                Some(w)
+            }
+            None => None,
+        };
+        let align: Option<Box<dyn Write>> = match &args.align_view {
+            Some(Some(path)) => Some(Box::new(BufWriter::new(File::create(path)?))),
+            Some(None) => Some(Box::new(io::stderr())),
+            None => None,
+        };
+        let header = output_header(args);
+        let color = args.format == Format::Tsv && args.output_template.is_none() && match args.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        };
+        let stdout = match args.output_template {
+            Some(_) => None,
+            None => Some(Sink::new(args.format, &header, Box::new(io::stdout().lock()))?),
+        };
+        let mut outputs = Outputs {
+            header,
+            stdout,
+            trace,
+            align,
+            color,
+            primer_hit: vec![false; n_primers],
+            grouped: Vec::new(),
+            n_products: 0,
+        };
+        if args.bedgraph {
+            outputs.stdout_sink("--bedgraph").write_row(&["track type=bedGraph name=mispriming".to_string()])?;
+        }
+        Ok(outputs)
+    }
+
+    /// The stdout sink, which every output but per-record files goes to;
+    /// `option` names the output for the panic message.
+    fn stdout_sink(&mut self, option: &str) -> &mut Sink {
+        self.stdout.as_mut().unwrap_or_else(|| panic!("{} writes to stdout", option))
+    }
+}
+
+/// One configured genome scan: the library scan plus the post-processing
+/// and output each record's hits go through.
+struct Scan<'a> {
+    args: &'a Args,
+    primers: &'a Primers,
+    opts: ScanOptions,
+    regions: Option<Regions>,
+    amplicon_pair: Option<(usize, usize)>,
+    funnel: Funnel,
+}
+
+impl Scan<'_> {
+    /// Scan one record and write its output. `offset` is where `seq` starts
+    /// in the full record (--region).
+    fn record(&self, out: &mut Outputs, seq_id: &str, mut seq: Cow<[u8]>, offset: usize) -> io::Result<()> {
+        let args = self.args;
+        let genome_len = seq.len();
+        if !args.alphabet.is_identity() {
+            args.alphabet.translate_in_place(seq.to_mut());
+        }
+        let intervals = match &self.regions {
+            Some(regions) => regions.for_record(seq_id, genome_len),
+            None if args.circular => {
+                // Append the first max_motif_len - 1 bases so windows spanning
+                // the origin are scanned; hits starting in the copy are repeats.
+                let wrap = self.primers.patterns.max_motif_len().saturating_sub(1).min(genome_len);
+                seq.to_mut().extend_from_within(..wrap);
+                vec![(0, seq.len())]
+            }
+            None => vec![(0, genome_len)],
+        };
+
+        let mut hits = search::scan_intervals(seq_id, &seq, &intervals, &self.primers.patterns, &self.opts, &self.funnel);
+        if let Some(w) = out.trace.as_mut() {
+            self.write_trace(w, offset)?;
+        }
+        if args.survey || args.seed_stats {
+            return Ok(());
+        }
+        hits.retain(|hit| hit.pos < genome_len);
+        // From here on positions are in record coordinates
+        for hit in &mut hits {
+            hit.pos += offset;
+        }
+        if let Some(pair) = self.amplicon_pair {
+            return self.write_amplicons(out, seq_id, &hits, pair);
+        }
+        let hits = self.reported_hits(hits);
+        for hit in &hits {
+            out.primer_hit[hit.motif_idx / 2] = true;
+        }
+        if args.count_only {
+            return self.write_counts(out.stdout_sink("--count-only"), seq_id, &hits);
+        }
+        if args.bedgraph {
+            return self.write_bedgraph(out.stdout_sink("--bedgraph"), seq_id, &hits);
+        }
+        self.write_hits(out, seq_id, hits, &seq, offset)
+    }
+
+    /// The funnel's seed trace for the record just scanned, in seed order.
+    fn write_trace(&self, w: &mut impl Write, offset: usize) -> io::Result<()> {
+        let mut trace = self.funnel.take_trace();
+        trace.sort_unstable_by_key(|t| (t.seed_pos, t.motif_idx, t.chunk));
+        for t in trace {
+            writeln!(w, "{}\t{}\t{}\t{}\t{}\t{}\t{}", t.seq_id, t.seed_pos + offset,
+                String::from_utf8_lossy(self.primers.patterns.motif(t.motif_idx)), Strand::of_motif(t.motif_idx),
+                t.offset, t.chunk, t.outcome)?;
+        }
+        w.flush()
+    }
+
+    /// --amplicon: one row per product of the `(fwd, rev)` primers within
+    /// --max-tm-diff.
+    fn write_amplicons(&self, out: &mut Outputs, seq_id: &str, hits: &[Hit], (fwd, rev): (usize, usize)) -> io::Result<()> {
+        let args = self.args;
+        let ids = &self.primers.ids;
+        let p = args.precision;
+        let (products, capped) = search::amplicons(hits, fwd, rev, args.max_product_size, args.max_amplicons);
+        if capped {
+            eprintln!("Warning: {} has more than {} amplicons; raise --max-amplicons to see all",
+                seq_id, args.max_amplicons);
+        }
+        let mut n_products = 0;
+        let sink = out.stdout_sink("--amplicon");
+        for amplicon in products {
+            let tm_diff = (amplicon.forward.tm - amplicon.reverse.tm).abs();
+            if args.max_tm_diff.is_some_and(|max| tm_diff > max) {
+                continue;
+            }
+            sink.write_row(&[
+                seq_id.to_string(),
+                (amplicon.start + usize::from(args.one_based)).to_string(),
+                amplicon.end.to_string(),
+                amplicon.size().to_string(),
+                ids[fwd].clone(),
+                format!("{:.*}", p, amplicon.forward.dg),
+                format!("{:.*}", p, amplicon.forward.tm),
+                ids[rev].clone(),
+                format!("{:.*}", p, amplicon.reverse.dg),
+                format!("{:.*}", p, amplicon.reverse.tm),
+                format!("{:.*}", p, tm_diff),
+            ])?;
+            n_products += 1;
+        }
+        sink.flush()?;
+        out.n_products += n_products;
+        Ok(())
+    }
+
+    /// A record's hits as reported: tandem repeats collapsed (--min-gap),
+    /// strands merged (--merge-strands) and capped at --max-hits overall.
+    fn reported_hits(&self, hits: Vec<Hit>) -> Vec<Hit> {
+        let args = self.args;
+        let mut hits = search::collapse_min_gap(hits, args.min_gap);
+        if args.merge_strands {
+            hits = search::merge_strands(hits, args.canonical);
+        }
+        if let Some(max) = args.max_hits {
+            hits.truncate(max.saturating_sub(self.funnel.reported.load(Ordering::Relaxed)));
+        }
+        Funnel::add(&self.funnel.reported, hits.len());
+        hits
+    }
+
+    /// --count-only: hits per primer and strand.
+    fn write_counts(&self, sink: &mut Sink, seq_id: &str, hits: &[Hit]) -> io::Result<()> {
+        let patterns = &self.primers.patterns;
+        // Motifs come in [forward, reverse complement] pairs, so the even
+        // index names the primer
+        let mut counts = vec![0usize; patterns.len()];
+        for hit in hits {
+            counts[hit.motif_idx] += 1;
+        }
+        for (motif_idx, &count) in counts.iter().enumerate().filter(|(_, &c)| c > 0) {
+            sink.write_row(&[
+                seq_id.to_string(),
+                String::from_utf8_lossy(patterns.motif(motif_idx - motif_idx % 2)).into_owned(),
+                Strand::of_motif(motif_idx).to_string(),
+                count.to_string(),
+            ])?;
+        }
+        sink.flush()
+    }
+
+    /// --bedgraph: the lowest hit dG at each covered position.
+    fn write_bedgraph(&self, sink: &mut Sink, seq_id: &str, hits: &[Hit]) -> io::Result<()> {
+        for (pos, dg) in search::min_dg_by_position(hits) {
+            sink.write_row(&[
+                seq_id.to_string(),
+                pos.to_string(),
+                (pos + 1).to_string(),
+                format!("{:.*}", self.args.precision, dg),
+            ])?;
+        }
+        sink.flush()
+    }
+
+    /// Hit rows, to the record's --output-template file or stdout, or held
+    /// for --group-by pattern; `seq` is the scanned sequence, starting at
+    /// `offset` in the record.
+    fn write_hits(&self, out: &mut Outputs, seq_id: &str, hits: Vec<Hit>, seq: &[u8], offset: usize) -> io::Result<()> {
+        let args = self.args;
+        let Outputs { header, stdout, align, color, grouped, .. } = out;
+        let mut record_sink = match &args.output_template {
+            Some(template) => {
+                let file = File::create(record_output_path(template, seq_id)?)?;
+                Some(Sink::new(args.format, header, Box::new(file))?)
+            }
+            None => None,
+        };
+        let sink = match record_sink.as_mut() {
+            Some(sink) => sink,
+            None => stdout.as_mut().expect("stdout sink exists without --output-template"),
+        };
+        for hit in hits {
+            let row = self.hit_row(&hit, seq, offset, *color);
+            if let Some(w) = align.as_mut() {
+                let window = &seq[hit.pos - offset..hit.pos - offset + hit.motif.len()];
+                writeln!(w, "# {}\t{}\t{}\t{:.*}", hit.seq_id, hit.pos + usize::from(args.one_based),
+                    hit.strand, args.precision, hit.dg)?;
+                writeln!(w, "{}", hit.motif)?;
+                writeln!(w, "{}", thal::match_bars(hit.motif.as_bytes(), window))?;
+                writeln!(w, "{}", String::from_utf8_lossy(window))?;
+            }
+            match args.group_by {
+                GroupBy::Position => sink.write_hit(&hit, &row)?,
+                GroupBy::Pattern => grouped.push((hit.motif_idx / 2, hit.dg, row)),
+            }
+        }
+        sink.flush()?;
+        if let Some(w) = align.as_mut() {
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    /// A hit's output row: `Hit::fields` plus the columns the options in
+    /// `args` add, in `output_header` order.
+    fn hit_row(&self, hit: &Hit, seq: &[u8], offset: usize, color: bool) -> Vec<String> {
+        let args = self.args;
+        let p = args.precision;
+        let motif = hit.motif.as_bytes();
+        let (start, end) = (hit.pos - offset, hit.pos - offset + motif.len());
+        let mut row = hit.fields(p);
+        row[1] = (hit.pos + usize::from(args.one_based)).to_string();
+        if color {
+            row[2] = color_dg(&row[2], hit.dg, args.threshold);
+        }
+        if args.wallace {
+            row.push(format!("{:.*}", p, thal::wallace_tm(motif)));
+        }
+        if let Some(gc) = hit.gc {
+            row.push(format!("{:.*}", p, gc));
+        }
+        if let Some(hairpins) = &self.primers.hairpin_dg {
+            let hairpin = hairpins[hit.motif_idx];
+            row.push(format!("{:.*}", p, hairpin));
+            row.push(format!("{:.*}", p, hit.dg - hairpin));
+        }
+        if let Some(halves) = &self.primers.half_dg {
+            let (five, three) = halves[hit.motif_idx / 2];
+            row.push(format!("{:.*}", p, five));
+            row.push(format!("{:.*}", p, three));
+        }
+        if args.normalized {
+            row.push(format!("{:.*}", p, thal::delta_g_per_bp(hit.dg, motif.len())));
+        }
+        if let Some(nn_tm) = hit.nn_tm {
+            row.push(format!("{:.*}", p, nn_tm));
+        }
+        if let (Some(window_dg), Some(primer_dg)) = (hit.window_dg, hit.primer_dg) {
+            row.push(format!("{:.*}", p, window_dg));
+            row.push(format!("{:.*}", p, primer_dg));
+        }
+        if args.rc_window {
+            row.push(String::from_utf8_lossy(&thal::reverse_complement(&seq[start..end])).into_owned());
+        }
+        if let Some(flank) = args.context {
+            let mut context = seq[start.saturating_sub(flank)..start].to_ascii_lowercase();
+            context.extend(seq[start..end].to_ascii_uppercase());
+            context.extend(seq[end..(end + flank).min(seq.len())].to_ascii_lowercase());
+            row.push(String::from_utf8_lossy(&context).into_owned());
+        }
+        if args.merge_strands {
+            row.push(hit.strand.to_string());
+            row.push(hit.opposite_dg.map_or(String::new(), |dg| format!("{:.*}", p, dg)));
+        }
+        if args.best_strand {
+            // Empty when the other strand was not scored (e.g. no GC clamp)
+            let opposite = hit.opposite_dg.map_or(String::new(), |dg| format!("{:.*}", p, dg));
+            let dg = format!("{:.*}", p, hit.dg);
+            let (fwd, rev) = match hit.strand {
+                Strand::Forward => (dg, opposite),
+                Strand::Reverse => (opposite, dg),
+            };
+            row.extend([hit.strand.to_string(), fwd, rev]);
+        }
+        if args.debug_chunks {
+            row.push(hit.chunk.to_string());
+            row.push(hit.chunk_offset.to_string());
+        }
+        row
+    }
+
+    /// The output held until every record is scanned: --group-by pattern
+    /// rows, --survey percentiles, --seed-stats counts and --report-no-hits.
+    fn finish(&self, out: &mut Outputs) -> io::Result<()> {
+        let args = self.args;
+        if args.group_by == GroupBy::Pattern {
+            let mut grouped = std::mem::take(&mut out.grouped);
+            // Stable sorts keep scan order within a primer unless --sort-dg
+            if args.sort_dg {
+                grouped.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+            } else {
+                grouped.sort_by_key(|g| g.0);
+            }
+            let sink = out.stdout_sink("--group-by pattern");
+            for (_, _, row) in &grouped {
+                sink.write_row(row)?;
+            }
+            sink.flush()?;
+        }
+        if let Some(survey) = &self.funnel.survey {
+            let sink = out.stdout_sink("--survey");
+            for percentile in SURVEY_PERCENTILES {
+                if let Some(dg) = survey.quantile(percentile / 100.0) {
+                    sink.write_row(&[percentile.to_string(), format!("{:.*}", args.precision, dg)])?;
+                }
+            }
+            sink.flush()?;
+            eprintln!("survey: {} scored windows, dG resolution {} kcal/mol", survey.count(), DgHistogram::BIN_WIDTH);
+        }
+        if let Some(counts) = &self.funnel.seed_counts {
+            let sink = out.stdout_sink("--seed-stats");
+            for (i, id) in self.primers.ids.iter().enumerate() {
+                let n = counts[2 * i].load(Ordering::Relaxed) + counts[2 * i + 1].load(Ordering::Relaxed);
+                sink.write_row(&[id.clone(), n.to_string()])?;
+            }
+            sink.flush()?;
+        }
+        if let Some(path) = &args.report_no_hits {
+            let mut w: Box<dyn Write> = match path {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stderr().lock()),
+            };
+            for (i, id) in self.primers.ids.iter().enumerate().filter(|&(i, _)| !out.primer_hit[i]) {
+                writeln!(w, "no hits\t{}\t{}", id, String::from_utf8_lossy(self.primers.patterns.motif(2 * i)))?;
+            }
+            w.flush()?;
+        }
+        Ok(())
+    }
+
+    /// --summary-json: the inputs, settings and funnel counts of the run.
+    fn write_summary(&self, path: &Path, genome: &str, n_records: usize, started: Instant) -> io::Result<()> {
+        let args = self.args;
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        let summary = RunSummary {
+            version: env!("CARGO_PKG_VERSION"),
+            genome,
+            patterns_file: args.patterns.as_deref(),
+            inline_patterns: &args.pattern,
+            parameters: SummaryParameters {
+                threshold: args.threshold,
+                min_dg: args.min_dg.is_finite().then_some(args.min_dg),
+                na_mm: args.na,
+                mg_mm: args.mg,
+                dntp_mm: args.dntp,
+                dnac_nm: args.dnac,
+                temp_c: args.temp,
+                max_loop: args.max_loop,
+                max_ambiguous: args.max_ambiguous,
+                chunk_size: args.chunk_size,
+                overlap: self.opts.chunk_overlap,
+                regions: args.regions.clone(),
+                conditions: args.conditions.clone(),
+                circular: args.circular,
+            },
+            patterns_loaded: self.primers.ids.len(),
+            patterns_skipped: self.primers.n_loaded - self.primers.ids.len(),
+            records: n_records,
+            seed_hits: load(&self.funnel.seed_hits),
+            full_length_windows: load(&self.funnel.full_length),
+            passing_windows: load(&self.funnel.passing),
+            hits_reported: load(&self.funnel.reported),
+            elapsed_seconds: started.elapsed().as_secs_f64(),
+        };
+        let mut w = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut w, &summary)?;
+        writeln!(w)?;
+        w.flush()
+    }
+}
+
+fn main() -> ExitCode {
+    exit::run_main(|| run(Args::parse()))
+}
+
+fn run(mut args: Args) -> io::Result<Outcome> {
+    let started = Instant::now();
+    if args.rna_as_dna {
+        args.alphabet.map_rna_to_dna();
+        eprintln!("Warning: --rna-as-dna scores U as T with DNA nearest-neighbor parameters");
+    }
+    check_output_flags(&args)?;
+    let thal_args = thal_args(&args);
+    if let Some(Command::Params) = args.command {
+        print_params(&thal_args);
+        return Ok(Outcome::Hits);
+    }
+
+    // Initialize thermodynamic parameters from primer3_config
+    rust_search::thal::ensure_parameters_loaded(PRIMER3_CONFIG)
+        .expect("Failed to load thermodynamic parameters");
+
+    if let Some(command) = &args.command {
+        return run_command(command, &args, &thal_args);
+    }
+
+    let conditions = match args.conditions.as_deref() {
+        Some(path) => Conditions::from_tsv(path)?,
+        None => Conditions::default(),
+    };
+    let primers = Primers::load(&args, &conditions, &thal_args)?;
+    let opts = scan_options(&args, primers.patterns.max_motif_len())?;
+    let regions = args.regions.as_deref().map(Regions::from_bed).transpose()?;
+    let seq_filter = SeqFilter::new(args.include_seqs.as_deref(), args.exclude_seqs.as_deref())?;
+    let genome = args.file.as_deref().expect("--file is required");
+    let (mut reader, record_offsets) = open_genome(&args, genome)?;
+    let scan = Scan {
+        args: &args,
+        primers: &primers,
+        opts,
+        regions,
+        amplicon_pair: primers.amplicon_pair(&args, &conditions, &thal_args)?,
+        funnel: scan_funnel(&args, primers.patterns.len()),
+    };
+    let mut out = Outputs::open(&args, primers.ids.len())?;
+
+    let mut n_records = 0;
+    while let Some(record) = reader.next() {
+        if exit::interrupted() {
+            break;
+        }
+        if args.max_hits.is_some_and(|max| scan.funnel.reported.load(Ordering::Relaxed) >= max) {
+            break;
+        }
+        let Some(rec) = fastx::check_record(record, genome, args.skip_errors)? else { continue };
+        n_records += 1;
+        let offset = record_offsets.get(n_records - 1).copied().unwrap_or(0);
+        let seq_id = String::from_utf8_lossy(rec.id()).to_string();
+        if !seq_filter.keeps(&seq_id) {
+            continue;
+        }
+        scan.record(&mut out, &seq_id, fastx::normalize_seq(rec.seq()), offset)?;
+    }
+    if n_records == 0 {
+        return Err(no_records(genome));
+    }
+    scan.finish(&mut out)?;
+    scan.funnel.report();
+    if let Some(path) = &args.summary_json {
+        scan.write_summary(path, genome, n_records, started)?;
+    }
+    let reported = scan.funnel.reported.load(Ordering::Relaxed);
+    if exit::interrupted() {
+        eprintln!("Interrupted: {} records scanned, {} hits reported", n_records, reported + out.n_products);
+        return Ok(Outcome::Interrupted);
+    }
+    // A survey or seed stats report on the scan, not hits
+    let found = args.survey || args.seed_stats || out.n_products > 0 || reported > 0;
+    Ok(if found { Outcome::Hits } else { Outcome::NoHits })
 }
@@ -1,127 +1,188 @@
 use clap::Parser;
-use needletail::{parse_fastx_file, Sequence};
-use rayon::prelude::*;
-use aho_corasick::AhoCorasick;
-use std::io;
-
+use std::io::{self, Write};
+use std::process::ExitCode;
+use rust_search::alphabet::Alphabet;
+use rust_search::exit::{self, Outcome};
+use rust_search::fastx::{self, PatternFormat};
+use rust_search::search::{self, Funnel, Patterns, ScanOptions, ScoreModel};
+use rust_search::thal::{self, Temperature, ThalArgs};
+
+/// Conditions shared across runs can be set once in the environment:
+/// a flag given on the command line wins over its `RUST_SEARCH_*`
+/// variable, which wins over the built-in default.
+///
+/// Hits are written record by record: each record's hits print in
+/// position order once it is fully scanned, and the output is flushed
+/// before the next record starts.
+///
+/// Exits 0 when hits are reported, 1 when the scan finds none, 2 on a
+/// usage or input error and 3 on an internal error. Ctrl-C stops the scan
+/// at its next chunk, writes the hits found so far and exits 130.
 #[derive(Parser, Debug)]
 struct Args {
     #[arg(short, long)] file: String,
     #[arg(short, long)] patterns: String,
+    /// Layout of the --patterns file: FASTA records, or txt with one
+    /// sequence per line
+    #[arg(long, value_enum, default_value_t = PatternFormat::Fasta)] pattern_format: PatternFormat,
     /// Max Delta G threshold (kcal/mol)
-    #[arg(short, long, default_value_t = -10.0)] threshold: f64,
+    #[arg(short, long, env = "RUST_SEARCH_THRESHOLD", default_value_t = -10.0)] threshold: f64,
+    /// Min Delta G (kcal/mol); hits are reported when min-dg <= dG <= threshold
+    #[arg(long, default_value_t = f64::NEG_INFINITY)] min_dg: f64,
+    /// Skip windows whose fraction of non-ACGT bases (e.g. N gaps) exceeds this
+    #[arg(long, default_value_t = 0.0)] max_ambiguous: f64,
     /// Monovalent salt Na+ (mM) - Primer3 default 50.0
-    #[arg(long, default_value_t = 50.0)] na: f64,
+    #[arg(long, env = "RUST_SEARCH_NA", default_value_t = 50.0)] na: f64,
     /// Divalent salt Mg2+ (mM) - Primer3 default 1.5
-    #[arg(long, default_value_t = 1.5)] mg: f64,
+    #[arg(long, env = "RUST_SEARCH_MG", default_value_t = 1.5)] mg: f64,
     /// dNTPs (mM) - Primer3 default 0.6
-    #[arg(long, default_value_t = 0.6)] dntp: f64,
+    #[arg(long, env = "RUST_SEARCH_DNTP", default_value_t = 0.6)] dntp: f64,
     /// Primer concentration (nM) - Primer3 default 200.0
-    #[arg(long, default_value_t = 200.0)] dnac: f64,
+    #[arg(long, env = "RUST_SEARCH_DNAC", default_value_t = 200.0)] dnac: f64,
     /// Temperature (C) for Delta G - default 37.0
-    #[arg(long, default_value_t = 37.0)] temp: f64,
-}
-
-struct ThermoParams {
-    dh: f64,
-    ds: f64,
+    #[arg(long, env = "RUST_SEARCH_TEMP", default_value_t = 37.0)] temp: f64,
+    /// Score the primer bound to the genomic window, mismatches included,
+    /// instead of the window's own perfect duplex (the two agree on a
+    /// perfect match); the window is then appended after the motif column
+    #[arg(long)] score_primer: bool,
+    /// Score every window as a heterodimer (C_T/4, no symmetry ΔS term),
+    /// palindromes included, to match tools that ignore self-complementarity
+    #[arg(long)] no_self_complementary_correction: bool,
+    /// Read U as T in patterns and genome, scoring RNA input with DNA
+    /// parameters (a warning is printed; there is no RNA model)
+    #[arg(long)] rna_as_dna: bool,
+    /// Decimal places for the dG, Tm, dH and dS columns
+    #[arg(long, default_value_t = 2)] precision: usize,
+    /// Warn about malformed FASTA/FASTQ records and keep going instead of
+    /// failing (the parser may be unable to resume past some errors)
+    #[arg(long)] skip_errors: bool,
 }
 
-// SantaLucia 1998 parameters
-fn get_nn_params(a: u8, b: u8) -> ThermoParams {
-    match (a, b) {
-        (b'A', b'A') | (b'T', b'T') => ThermoParams { dh: -7.9, ds: -22.2 },
-        (b'A', b'T') => ThermoParams { dh: -7.2, ds: -20.4 },
-        (b'T', b'A') => ThermoParams { dh: -7.2, ds: -21.3 },
-        (b'C', b'A') | (b'T', b'G') => ThermoParams { dh: -8.5, ds: -22.7 },
-        (b'G', b'T') | (b'A', b'C') => ThermoParams { dh: -8.4, ds: -22.4 },
-        (b'C', b'T') | (b'A', b'G') => ThermoParams { dh: -7.8, ds: -21.0 },
-        (b'G', b'A') | (b'T', b'C') => ThermoParams { dh: -8.2, ds: -22.2 },
-        (b'C', b'G') => ThermoParams { dh: -10.6, ds: -27.2 },
-        (b'G', b'C') => ThermoParams { dh: -9.8, ds: -24.4 },
-        (b'C', b'C') | (b'G', b'G') => ThermoParams { dh: -8.0, ds: -19.9 },
-        _ => ThermoParams { dh: 0.0, ds: 0.0 },
+/// The library conditions for `args`, shared by every scored window
+fn thal_args(args: &Args) -> ThalArgs {
+    ThalArgs {
+        mv: args.na,
+        dv: args.mg,
+        dntp: args.dntp,
+        dna_conc: args.dnac,
+        temp: Temperature::celsius(args.temp),
+        symmetry_correction: !args.no_self_complementary_correction,
+        ..thal::create_default_args()
     }
 }
 
-fn calculate_thermo(seq: &[u8], args: &Args) -> (f64, f64) {
-    let mut total_dh = 0.0;
-    let mut total_ds = 0.0;
-
-    // Initiation (SantaLucia 1998)
-    total_dh += 0.2;
-    total_ds += -5.7;
-
-    // Nearest Neighbor sum
-    for i in 0..seq.len() - 1 {
-        let p = get_nn_params(seq[i], seq[i+1]);
-        total_dh += p.dh;
-        total_ds += p.ds;
-    }
-
-    // Salt correction (Santalucia 2004 / Primer3 default style)
-    // Effect on Delta S: ds_corrected = ds + 0.368 * (N-1) * ln([Na_equivalent])
-    let na_eq = args.na + 120.0 * (args.mg - args.dntp).sqrt();
-    let salt_corr = 0.368 * (seq.len() as f64 - 1.0) * (na_eq / 1000.0).ln();
-    total_ds += salt_corr;
-
-    let t_kelvin = args.temp + 273.15;
-    let delta_g = total_dh - (t_kelvin * total_ds / 1000.0);
-
-    // Tm calculation for Heterodimer
-    let r = 1.9872; // gas constant cal/(K*mol)
-    let c = args.dnac / 1e9;
-    let tm = (1000.0 * total_dh) / (total_ds + r * (c / 4.0).ln()) - 273.15;
-
-    (delta_g, tm)
+fn main() -> ExitCode {
+    exit::run_main(|| run(Args::parse()))
 }
 
-fn main() -> io::Result<()> {
-    let args = Args::parse();
-    let mut pattern_reader = parse_fastx_file(&args.patterns).expect("Invalid pattern file");
-    let mut all_motifs = Vec::new();
-    let mut all_seeds = Vec::new();
-
-    while let Some(record) = pattern_reader.next() {
-        let rec = record.unwrap();
-        let seq = rec.seq().to_ascii_uppercase();
-        let rc = rec.reverse_complement().to_ascii_uppercase();
-        for s in vec![seq, rc] {
-            all_seeds.push(s[0..7].to_vec()); // 7-mer seed
-            all_motifs.push(s);
+fn run(args: Args) -> io::Result<Outcome> {
+    let mut alphabet = Alphabet::default();
+    if args.rna_as_dna {
+        alphabet.map_rna_to_dna();
+        eprintln!("Warning: --rna-as-dna scores U as T with DNA nearest-neighbor parameters");
+    }
+    let no_patterns = || io::Error::new(io::ErrorKind::InvalidInput, "no patterns loaded");
+    let mut primers = Vec::new();
+    match args.pattern_format {
+        PatternFormat::Fasta => {
+            let mut pattern_reader = match fastx::open(&args.patterns) {
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(no_patterns()),
+                reader => reader?,
+            };
+            while let Some(record) = pattern_reader.next() {
+                let Some(rec) = fastx::check_record(record, &args.patterns, args.skip_errors)? else { continue };
+                primers.push(fastx::normalize_seq(rec.seq()).into_owned());
+            }
         }
+        PatternFormat::Txt => primers = fastx::read_text_patterns(&args.patterns)?,
     }
-
-    let ac = AhoCorasick::new(&all_seeds).unwrap();
-    let mut reader = parse_fastx_file(&args.file).expect("Genome file error");
-    let chunk_size = 1_000_000;
-    let overlap = 100;
+    if primers.is_empty() {
+        return Err(no_patterns());
+    }
+    let thal_args = thal_args(&args);
+    let patterns = Patterns::new(primers.into_iter().map(|mut seq| {
+        alphabet.translate_in_place(&mut seq);
+        (seq, thal_args.clone())
+    }));
+    let opts = ScanOptions {
+        threshold: args.threshold,
+        min_dg: args.min_dg,
+        max_ambiguous: args.max_ambiguous,
+        model: if args.score_primer { ScoreModel::PrimerOnWindow } else { ScoreModel::WindowDuplex },
+        cancel: Some(exit::handle_interrupt()?),
+        ..ScanOptions::default()
+    };
+
+    let no_records = || io::Error::new(io::ErrorKind::InvalidInput, format!("no genome records in {}", args.file));
+    let mut reader = match fastx::open(&args.file) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Err(no_records()),
+        reader => reader?,
+    };
+    let mut n_records = 0;
+    let mut n_hits = 0;
+    let funnel = Funnel::default();
 
     while let Some(record) = reader.next() {
-        let rec = record.unwrap();
+        if exit::interrupted() {
+            break;
+        }
+        let Some(rec) = fastx::check_record(record, &args.file, args.skip_errors)? else { continue };
+        n_records += 1;
         let seq_id = String::from_utf8_lossy(rec.id()).to_string();
-        let full_seq = rec.seq();
+        let mut full_seq = fastx::normalize_seq(rec.seq());
+        if !alphabet.is_identity() {
+            alphabet.translate_in_place(full_seq.to_mut());
+        }
+        let hits = search::scan_intervals(&seq_id, &full_seq, &[(0, full_seq.len())], &patterns, &opts, &funnel);
+        // Same columns as main_fullsalt: dG/dH in kcal/mol, dS in cal/K/mol
+        let mut out = io::stdout().lock();
+        for hit in &hits {
+            write!(out, "{:.*}", args.precision, hit)?;
+            if args.score_primer {
+                write!(out, "\t{}", String::from_utf8_lossy(&full_seq[hit.pos..hit.pos + hit.motif.len()]))?;
+            }
+            writeln!(out)?;
+        }
+        n_hits += hits.len();
+        // Each record's output is complete before the next is scanned
+        out.flush()?;
+    }
+    if n_records == 0 {
+        return Err(no_records());
+    }
+    if exit::interrupted() {
+        eprintln!("Interrupted: {} records scanned, {} hits reported", n_records, n_hits);
+        return Ok(Outcome::Interrupted);
+    }
+    Ok(if n_hits > 0 { Outcome::Hits } else { Outcome::NoHits })
+}
 
-        (0..full_seq.len()).into_par_iter().step_by(chunk_size - overlap).for_each(|start| {
-            let end = (start + chunk_size).min(full_seq.len());
-            let chunk = full_seq[start..end].to_ascii_uppercase();
-            
-            for mat in ac.find_iter(&chunk) {
-                let motif = &all_motifs[mat.pattern().as_usize()];
-                let hit_pos = mat.start();
-                let v_end = (hit_pos + motif.len()).min(chunk.len());
-                let vicinity = &chunk[hit_pos..v_end];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                if vicinity.len() == motif.len() {
-                    let (dg, tm) = calculate_thermo(vicinity, &args);
-                    if dg <= args.threshold {
-                        println!("{}\t{}\t{:.2}\t{:.2}\t{}", 
-                            seq_id, start + hit_pos, dg, tm, 
-                            String::from_utf8_lossy(motif));
-                    }
-                }
-            }
-        });
+    const BASE: [&str; 5] = ["main_simplesalt", "--file", "genome.fa", "--patterns", "primers.fa"];
+
+    #[test]
+    fn test_thal_args_carry_the_command_line_conditions() {
+        let args = Args::parse_from([&BASE[..], &["--na", "40", "--mg", "2", "--dntp", "0.2", "--dnac", "100", "--temp", "60"]].concat());
+        let t = thal_args(&args);
+        assert_eq!((t.mv, t.dv, t.dntp, t.dna_conc), (40.0, 2.0, 0.2, 100.0));
+        assert_eq!(t.temp.as_celsius(), 60.0);
+        assert!(t.symmetry_correction);
+    }
+
+    #[test]
+    fn test_no_self_complementary_correction_uses_heterodimer_terms() {
+        let args = thal_args(&Args::parse_from(BASE));
+        let naive = thal_args(&Args::parse_from([&BASE[..], &["--no-self-complementary-correction"]].concat()));
+        assert!(!naive.symmetry_correction);
+        let palindrome = b"GAATTCGAATTC";
+        let (corrected, forced) = (thal::calculate_thermo(palindrome, &args), thal::calculate_thermo(palindrome, &naive));
+        assert!((corrected.ds - forced.ds - -1.4).abs() < 1e-9);
+        assert!(forced.temp < corrected.temp);
+        // Non-palindromes are unaffected
+        let seq = b"ATCGATCGATCG";
+        assert_eq!(thal::calculate_thermo(seq, &args).temp, thal::calculate_thermo(seq, &naive).temp);
     }
-    Ok(())
 }
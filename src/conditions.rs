@@ -0,0 +1,121 @@
+use crate::thal::{Temperature, ThalArgs};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+/// Reaction conditions for one pattern; unset fields keep the global value.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Overrides {
+    mv: Option<f64>,
+    dv: Option<f64>,
+    dntp: Option<f64>,
+    dna_conc: Option<f64>,
+    /// °C, as on the command line.
+    temp: Option<f64>,
+}
+
+/// Per-pattern reaction conditions read from a TSV sidecar keyed by pattern
+/// id. The header names the columns: `id` first, then any of `mv`, `dv`,
+/// `dntp`, `dnac` (nM) and `temp` (°C) in any order.
+#[derive(Debug, Default, Clone)]
+pub struct Conditions {
+    by_id: HashMap<String, Overrides>,
+}
+
+impl Conditions {
+    pub fn from_tsv(path: &str) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse the sidecar; `#` lines and blanks are ignored and empty cells
+    /// fall back to the global value.
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut columns: Option<Vec<String>> = None;
+        let mut by_id = HashMap::new();
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |what: String| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("conditions line {}: {}", lineno + 1, what),
+                )
+            };
+            let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+            let Some(columns) = &columns else {
+                if fields[0] != "id" {
+                    return Err(bad("header must start with an id column".to_string()));
+                }
+                for name in &fields[1..] {
+                    if !matches!(*name, "mv" | "dv" | "dntp" | "dnac" | "temp") {
+                        return Err(bad(format!("unknown column '{}'", name)));
+                    }
+                }
+                columns = Some(fields.iter().map(|f| f.to_string()).collect());
+                continue;
+            };
+
+            let mut overrides = Overrides::default();
+            for (name, value) in columns.iter().zip(&fields).skip(1) {
+                if value.is_empty() {
+                    continue;
+                }
+                let value: f64 = value
+                    .parse()
+                    .map_err(|_| bad(format!("invalid {} '{}'", name, value)))?;
+                let slot = match name.as_str() {
+                    "mv" => &mut overrides.mv,
+                    "dv" => &mut overrides.dv,
+                    "dntp" => &mut overrides.dntp,
+                    "dnac" => &mut overrides.dna_conc,
+                    _ => &mut overrides.temp,
+                };
+                *slot = Some(value);
+            }
+            by_id.insert(fields[0].to_string(), overrides);
+        }
+        Ok(Conditions { by_id })
+    }
+
+    /// `global` with the conditions listed for `id` applied, or `global`
+    /// unchanged if the pattern is not listed.
+    pub fn args_for(&self, id: &str, global: &ThalArgs) -> ThalArgs {
+        let mut args = global.clone();
+        if let Some(o) = self.by_id.get(id) {
+            args.mv = o.mv.unwrap_or(args.mv);
+            args.dv = o.dv.unwrap_or(args.dv);
+            args.dntp = o.dntp.unwrap_or(args.dntp);
+            args.dna_conc = o.dna_conc.unwrap_or(args.dna_conc);
+            args.temp = o.temp.map_or(args.temp, Temperature::celsius);
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::thal::create_default_args;
+
+    #[test]
+    fn test_args_for_overrides_listed_fields() {
+        let tsv = "# multiplex\nid\tdv\ttemp\tmv\nfwd\t3.0\t60\t\n";
+        let conditions = Conditions::parse(tsv.as_bytes()).unwrap();
+        let global = create_default_args();
+        let fwd = conditions.args_for("fwd", &global);
+        assert_eq!(fwd.dv, 3.0);
+        assert_eq!(fwd.temp, Temperature::celsius(60.0));
+        assert_eq!(fwd.mv, global.mv);
+        assert_eq!(conditions.args_for("rev", &global).dv, global.dv);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_lines() {
+        assert!(Conditions::parse("name\tmv\n".as_bytes()).is_err());
+        assert!(Conditions::parse("id\tsalt\n".as_bytes()).is_err());
+        assert!(Conditions::parse("id\tmv\nfwd\tlots\n".as_bytes()).is_err());
+    }
+}
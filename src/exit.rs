@@ -0,0 +1,81 @@
+//! Exit status shared by the binaries, so pipelines can branch on a run:
+//!
+//! - 0: hits were reported, or a subcommand succeeded
+//! - 1: the scan completed but reported no hits
+//! - 2: usage or input error (bad flags, missing or malformed files);
+//!   clap's own usage errors already exit with 2
+//! - 3: internal error (a failed self-test, an output failure, a panic)
+//! - 130: interrupted by Ctrl-C after flushing partial results
+
+use std::io;
+use std::panic::{self, UnwindSafe};
+use std::process::{self, ExitCode};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const HITS: u8 = 0;
+pub const NO_HITS: u8 = 1;
+pub const INPUT_ERROR: u8 = 2;
+pub const INTERNAL_ERROR: u8 = 3;
+/// 128 + SIGINT, as a shell reports a process killed by Ctrl-C.
+pub const INTERRUPTED: u8 = 130;
+
+static INTERRUPT: AtomicBool = AtomicBool::new(false);
+
+/// How a run that did not fail ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Hits were reported (or there was nothing to search for, as in a
+    /// subcommand or a survey).
+    Hits,
+    NoHits,
+    /// Ctrl-C stopped the run early; what was found has been written.
+    Interrupted,
+}
+
+/// Catch Ctrl-C so a scan can stop at its next chunk and flush and report
+/// what it has instead of losing buffered output; see `interrupted`. A
+/// second Ctrl-C exits at once. Returns the flag Ctrl-C sets, for
+/// `ScanOptions::cancel`.
+pub fn handle_interrupt() -> io::Result<&'static AtomicBool> {
+    ctrlc::set_handler(|| {
+        if INTERRUPT.swap(true, Ordering::Relaxed) {
+            process::exit(INTERRUPTED.into());
+        }
+    })
+    .map_err(io::Error::other)?;
+    Ok(&INTERRUPT)
+}
+
+/// Whether Ctrl-C has been pressed since `handle_interrupt`.
+pub fn interrupted() -> bool {
+    INTERRUPT.load(Ordering::Relaxed)
+}
+
+/// Exit status for a failed run: the error kinds raised for bad arguments
+/// and unreadable or malformed input are the caller's to fix.
+pub fn error_code(e: &io::Error) -> u8 {
+    match e.kind() {
+        io::ErrorKind::InvalidInput
+        | io::ErrorKind::InvalidData
+        | io::ErrorKind::NotFound
+        | io::ErrorKind::PermissionDenied
+        | io::ErrorKind::UnexpectedEof => INPUT_ERROR,
+        _ => INTERNAL_ERROR,
+    }
+}
+
+/// Run a binary's body and map its result to the exit status, printing an
+/// error as a plain message rather than main's Debug formatting.
+pub fn run_main(run: impl FnOnce() -> io::Result<Outcome> + UnwindSafe) -> ExitCode {
+    match panic::catch_unwind(run) {
+        Ok(Ok(Outcome::Hits)) => ExitCode::from(HITS),
+        Ok(Ok(Outcome::NoHits)) => ExitCode::from(NO_HITS),
+        Ok(Ok(Outcome::Interrupted)) => ExitCode::from(INTERRUPTED),
+        Ok(Err(e)) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(error_code(&e))
+        }
+        // The panic hook has already printed the message
+        Err(_) => ExitCode::from(INTERNAL_ERROR),
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+
+/// One line of a samtools `.fai` index: where a record's sequence starts
+/// in the FASTA and how its lines are wrapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaiEntry {
+    /// Bases in the record.
+    pub length: usize,
+    /// Byte offset of the record's first base.
+    pub offset: u64,
+    /// Bases per full line.
+    pub line_bases: usize,
+    /// Bytes per full line, line terminator included.
+    pub line_width: usize,
+}
+
+/// A `.fai` index, so known loci can be read by seeking instead of
+/// streaming the whole FASTA. Only uncompressed FASTA is supported.
+#[derive(Debug, Default, Clone)]
+pub struct Faidx {
+    entries: HashMap<String, FaiEntry>,
+}
+
+impl Faidx {
+    pub fn from_path(path: &str) -> io::Result<Self> {
+        let file = File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        Self::parse(BufReader::new(file))
+    }
+
+    /// Parse `.fai` lines (name, length, offset, linebases, linewidth; any
+    /// further FASTQ columns are ignored).
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut entries = HashMap::new();
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bad = || io::Error::new(io::ErrorKind::InvalidData, format!("fai line {}: expected 5 columns", lineno + 1));
+            let fields: Vec<&str> = line.trim_end().split('\t').collect();
+            if fields.len() < 5 {
+                return Err(bad());
+            }
+            let num = |i: usize| fields[i].parse::<usize>().map_err(|_| bad());
+            let entry = FaiEntry {
+                length: num(1)?,
+                offset: num(2)? as u64,
+                line_bases: num(3)?,
+                line_width: num(4)?,
+            };
+            if entry.line_bases == 0 || entry.line_width < entry.line_bases {
+                return Err(bad());
+            }
+            entries.insert(fields[0].to_string(), entry);
+        }
+        Ok(Faidx { entries })
+    }
+
+    pub fn get(&self, seq_id: &str) -> Option<&FaiEntry> {
+        self.entries.get(seq_id)
+    }
+
+    /// Bases `[start, end)` of `seq_id` read from `fasta`, with `end`
+    /// clamped to the record length and line terminators dropped.
+    pub fn fetch<R: Read + Seek>(&self, fasta: &mut R, seq_id: &str, start: usize, end: usize) -> io::Result<Vec<u8>> {
+        let entry = self.get(seq_id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not in the FASTA index", seq_id))
+        })?;
+        let end = end.min(entry.length);
+        if start >= end {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "{}:{}-{} is outside the {} bp record", seq_id, start + 1, end, entry.length)));
+        }
+        let byte_of = |base: usize| {
+            entry.offset + (base / entry.line_bases * entry.line_width + base % entry.line_bases) as u64
+        };
+        let first = byte_of(start);
+        let last = byte_of(end - 1);
+        let mut raw = vec![0; (last - first + 1) as usize];
+        fasta.seek(SeekFrom::Start(first))?;
+        fasta.read_exact(&mut raw)?;
+        raw.retain(|b| !b.is_ascii_whitespace());
+        Ok(raw)
+    }
+}
+
+/// A samtools-style locus: `chr1:1,000-2,000` (1-based, inclusive) or a
+/// bare `chr1` for the whole record. Held 0-based half-open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locus {
+    pub seq_id: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Locus {
+    pub fn parse(arg: &str) -> io::Result<Self> {
+        let bad = || io::Error::new(io::ErrorKind::InvalidInput, format!("invalid region {:?}; expected CHR:START-END", arg));
+        let whole = || Locus { seq_id: arg.to_string(), start: 0, end: usize::MAX };
+        // A name may itself contain ':', so only a numeric range suffix counts
+        let Some((seq_id, range)) = arg.rsplit_once(':') else { return Ok(whole()) };
+        let Some((start, end)) = range.split_once('-') else { return Ok(whole()) };
+        let num = |s: &str| s.replace(',', "").parse::<usize>().ok();
+        match (num(start), num(end)) {
+            (Some(start), Some(end)) if start >= 1 && end >= start => {
+                Ok(Locus { seq_id: seq_id.to_string(), start: start - 1, end })
+            }
+            (Some(_), Some(_)) => Err(bad()),
+            _ => Ok(whole()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_spans_wrapped_lines() {
+        // 10 bases per line, CRLF terminators
+        let fasta = b">chr1\r\nACGTACGTAA\r\nCCGGTTAACC\r\nGG\r\n";
+        let index = Faidx::parse("chr1\t22\t7\t10\t12\n".as_bytes()).unwrap();
+        let mut reader = io::Cursor::new(&fasta[..]);
+        assert_eq!(index.fetch(&mut reader, "chr1", 0, 4).unwrap(), b"ACGT");
+        assert_eq!(index.fetch(&mut reader, "chr1", 8, 13).unwrap(), b"AACCG");
+        assert_eq!(index.fetch(&mut reader, "chr1", 20, 100).unwrap(), b"GG");
+        assert!(index.fetch(&mut reader, "chr1", 22, 30).is_err());
+        assert!(index.fetch(&mut reader, "chr2", 0, 1).is_err());
+    }
+
+    #[test]
+    fn test_locus_parse() {
+        assert_eq!(Locus::parse("chr1:1,000-2000").unwrap(), Locus { seq_id: "chr1".into(), start: 999, end: 2000 });
+        assert_eq!(Locus::parse("chr1").unwrap(), Locus { seq_id: "chr1".into(), start: 0, end: usize::MAX });
+        assert_eq!(Locus::parse("HLA:A").unwrap().seq_id, "HLA:A");
+        assert!(Locus::parse("chr1:200-100").is_err());
+        assert!(Locus::parse("chr1:0-100").is_err());
+    }
+}
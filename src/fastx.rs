@@ -0,0 +1,111 @@
+use needletail::errors::{ParseError, ParseErrorKind};
+use needletail::parser::SequenceRecord;
+use needletail::{parse_fastx_file, parse_fastx_reader, FastxReader};
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io;
+use crate::faidx::{Faidx, Locus};
+
+/// Layout of a patterns file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum PatternFormat {
+    /// FASTA or FASTQ records, named by their ids
+    #[default]
+    Fasta,
+    /// One sequence per line; blank lines and `#` comments are skipped
+    Txt,
+}
+
+/// Open a FASTA/FASTQ file, naming `path` in the error instead of panicking.
+/// An empty file is reported with `io::ErrorKind::UnexpectedEof` so callers
+/// can tell it apart from unreadable input.
+pub fn open(path: &str) -> io::Result<Box<dyn FastxReader>> {
+    parse_fastx_file(path).map_err(|e| open_error(path, e))
+}
+
+fn open_error(path: &str, e: ParseError) -> io::Error {
+    let kind = match e.kind {
+        ParseErrorKind::EmptyFile => io::ErrorKind::UnexpectedEof,
+        _ => io::ErrorKind::InvalidData,
+    };
+    io::Error::new(kind, format!("{}: {}", path, e))
+}
+
+/// Open a FASTA/FASTQ file through a read-only memory map, so repeated runs
+/// over one reference are served from the page cache without read copies.
+/// Compressed input is still detected and decompressed.
+pub fn open_mmap(path: &str) -> io::Result<Box<dyn FastxReader>> {
+    let file = File::open(path)?;
+    // SAFETY: the map is read-only; the file must not be truncated while
+    // the scan runs, as with any mapped input
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    parse_fastx_reader(io::Cursor::new(map)).map_err(|e| open_error(path, e))
+}
+
+/// Read just `loci` of the FASTA at `path` by seeking through `index`, as
+/// one record per locus named by its sequence id. Also returns each
+/// record's start in the full sequence, to add to reported positions.
+pub fn open_loci(path: &str, index: &Faidx, loci: &[Locus]) -> io::Result<(Box<dyn FastxReader>, Vec<usize>)> {
+    let mut file = File::open(path)?;
+    let mut fasta = Vec::new();
+    for locus in loci {
+        fasta.extend_from_slice(format!(">{}\n", locus.seq_id).as_bytes());
+        fasta.extend(index.fetch(&mut file, &locus.seq_id, locus.start, locus.end)?);
+        fasta.push(b'\n');
+    }
+    let reader = parse_fastx_reader(io::Cursor::new(fasta)).map_err(|e| open_error(path, e))?;
+    Ok((reader, loci.iter().map(|locus| locus.start).collect()))
+}
+
+/// Malformed-record policy for one reader result. With `skip_errors` the
+/// error (which names the record and line) is logged and `None` returned;
+/// otherwise it becomes an `io::Error` naming the file. The parser may not
+/// be able to resume after a malformed record, in which case the next call
+/// to `next()` simply ends the file.
+pub fn check_record<'a>(
+    record: Result<SequenceRecord<'a>, ParseError>,
+    path: &str,
+    skip_errors: bool,
+) -> io::Result<Option<SequenceRecord<'a>>> {
+    match record {
+        Ok(rec) => Ok(Some(rec)),
+        Err(e) if skip_errors => {
+            eprintln!("Skipping malformed record in {}: {}", path, e);
+            Ok(None)
+        }
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("malformed record in {}: {} (use --skip-errors to continue)", path, e),
+        )),
+    }
+}
+
+/// A record's sequence as every binary scans it: uppercased, with any
+/// whitespace the parser leaves inside wrapped lines (stray `\r`, spaces,
+/// tabs) removed. Already-clean sequence is passed through without a copy.
+pub fn normalize_seq(seq: Cow<'_, [u8]>) -> Cow<'_, [u8]> {
+    if !seq.iter().any(|b| b.is_ascii_lowercase() || b.is_ascii_whitespace()) {
+        return seq;
+    }
+    Cow::Owned(seq.iter().filter(|b| !b.is_ascii_whitespace()).map(u8::to_ascii_uppercase).collect())
+}
+
+/// The sequences of a one-per-line patterns file (`PatternFormat::Txt`),
+/// normalized like record sequence. A `>` line means the file is FASTA and
+/// is rejected rather than loaded as a pattern.
+pub fn read_text_patterns(path: &str) -> io::Result<Vec<Vec<u8>>> {
+    let text = fs::read_to_string(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    let mut patterns = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('>') {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "{} line {} is a FASTA header; use --pattern-format fasta", path, n + 1)));
+        }
+        patterns.push(normalize_seq(line.as_bytes().into()).into_owned());
+    }
+    Ok(patterns)
+}
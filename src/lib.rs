@@ -1,6 +1,14 @@
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int, c_double};
 
+pub mod alphabet;
+pub mod conditions;
+pub mod exit;
+pub mod faidx;
+pub mod fastx;
+pub mod regions;
+pub mod search;
+
 pub mod thal {
     use super::*;
 
@@ -88,6 +96,19 @@ pub mod thal {
         pub fn destroy_thal_structures();
     }
 
+    /// Salt correction used by the native NN model, mirroring primer3's
+    /// `salt_corrections_method`. `thal` always applies its own correction.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+    pub enum SaltMethod {
+        /// Schildkraut & Lifson 1965: Tm + 16.6·log10([Na+])
+        Schildkraut,
+        /// SantaLucia 1998: ΔS + 0.368·(N-1)·ln([Na+]) (primer3 default)
+        #[default]
+        Santalucia,
+        /// Owczarzy et al. 2004: GC-dependent 1/Tm correction
+        Owczarzy,
+    }
+
     #[derive(Debug, Clone)]
     pub struct ThalArgs {
         pub alignment_type: ThalAlignmentType,
@@ -96,8 +117,15 @@ pub mod thal {
         pub dv: f64,
         pub dntp: f64,
         pub dna_conc: f64,
-        pub temp: f64,
+        /// Temperature for ΔG (and primer3's structure search).
+        pub temp: Temperature,
         pub dimer: i32,
+        /// Native model only; ignored by `thal`.
+        pub salt_method: SaltMethod,
+        /// Native model only: score self-complementary duplexes with C_T
+        /// and the symmetry ΔS term. When false every duplex is scored as a
+        /// heterodimer (C_T/4), as by tools that ignore self-complementarity.
+        pub symmetry_correction: bool,
     }
 
     #[derive(Debug, Clone)]
@@ -112,7 +140,57 @@ pub mod thal {
         pub sec_struct: Option<String>,
     }
 
-    pub const ABSOLUTE_ZERO: f64 = 273.15;
+    impl ThalResults {
+        /// True when `temp`, `dg`, `ds` and `dh` each differ from `other` by at
+        /// most `tol`, in the fields' own units (°C, cal/mol, cal/K/mol), so
+        /// pick `tol` for the coarsest field being compared. Identical values
+        /// (including two `THAL_ERROR_SCORE` Tms) always match.
+        pub fn approx_eq(&self, other: &ThalResults, tol: f64) -> bool {
+            let close = |a: f64, b: f64| a == b || (a - b).abs() <= tol;
+            close(self.temp, other.temp)
+                && close(self.dg, other.dg)
+                && close(self.ds, other.ds)
+                && close(self.dh, other.dh)
+        }
+    }
+
+    /// Gas constant, cal/(K·mol).
+    pub const R_CAL: f64 = 1.9872;
+    /// °C to K offset.
+    pub const KELVIN_OFFSET: f64 = 273.15;
+    /// Older name for `KELVIN_OFFSET`.
+    pub const ABSOLUTE_ZERO: f64 = KELVIN_OFFSET;
+    /// A temperature whose unit is chosen at construction and at every
+    /// read, so a °C value can never be taken for Kelvin or the reverse.
+    #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+    pub struct Temperature(f64);
+
+    impl Temperature {
+        pub fn celsius(c: f64) -> Self {
+            Temperature(c + KELVIN_OFFSET)
+        }
+
+        pub fn kelvin(k: f64) -> Self {
+            Temperature(k)
+        }
+
+        pub fn as_celsius(self) -> f64 {
+            self.0 - KELVIN_OFFSET
+        }
+
+        pub fn as_kelvin(self) -> f64 {
+            self.0
+        }
+    }
+
+    /// Free Mg2+ counts as `DIVALENT_COEFF`·sqrt([Mg2+]) mM Na+ (von Ahsen
+    /// 2001, as in primer3).
+    pub const DIVALENT_COEFF: f64 = 120.0;
+    /// SantaLucia 1998 salt term: ΔS += `SALT_ENTROPY_COEFF`·(N-1)·ln([Na+]).
+    pub const SALT_ENTROPY_COEFF: f64 = 0.368;
+    /// ΔG/ΔH unit conversion: the native model and `thal` work in cal/mol,
+    /// reports in kcal/mol.
+    pub const CAL_PER_KCAL: f64 = 1000.0;
     pub const THAL_ERROR_SCORE: f64 = f64::NEG_INFINITY;
 
     use std::sync::Mutex;
@@ -165,7 +243,7 @@ pub mod thal {
             dv: args.dv,
             dntp: args.dntp,
             dna_conc: args.dna_conc,
-            temp: args.temp,
+            temp: args.temp.as_kelvin(),
             dimer: args.dimer,
         };
 
@@ -206,6 +284,29 @@ pub mod thal {
     // Re-expose the thal function as the wrapper
     pub use thal_wrapper as thal;
 
+    /// primer3 hairpin of `seq` folding on itself under `args`' conditions
+    /// (as primer3-py `calc_hairpin`); `dg` is 0 when no structure forms.
+    pub fn hairpin(seq: &[u8], args: &ThalArgs) -> ThalResults {
+        let hairpin_args = ThalArgs { alignment_type: ThalAlignmentType::Hairpin, dimer: 0, ..args.clone() };
+        thal(seq, seq, &hairpin_args, ThalMode::Fast)
+    }
+
+    /// Best heteroduplex between two explicit oligos (as primer3-py
+    /// `calc_heterodimer`), whatever `args.alignment_type` says. Runs in
+    /// `ThalMode::General` so `sec_struct` is filled in; `align_end_1` and
+    /// `align_end_2` are primer3's 1-based end of the alignment in `seq1`
+    /// and `seq2` respectively, 0 when no duplex forms.
+    pub fn heterodimer(seq1: &[u8], seq2: &[u8], args: &ThalArgs) -> Result<ThalResults, ThalError> {
+        for seq in [seq1, seq2] {
+            if seq.is_empty() {
+                return Err(ThalError::EmptySequence);
+            }
+            validate_bases(seq)?;
+        }
+        let dimer_args = ThalArgs { alignment_type: ThalAlignmentType::Any, dimer: 1, ..args.clone() };
+        Ok(thal(seq1, seq2, &dimer_args, ThalMode::General))
+    }
+
     pub fn create_default_args() -> ThalArgs {
         ThalArgs {
             alignment_type: ThalAlignmentType::Any,
@@ -214,9 +315,996 @@ pub mod thal {
             dv: 0.0,
             dntp: 0.8,
             dna_conc: 50.0,
-            temp: 37.0 + ABSOLUTE_ZERO,
+            temp: Temperature::celsius(37.0),
             dimer: 1,
+            salt_method: SaltMethod::Santalucia,
+            symmetry_correction: true,
+        }
+    }
+
+    /// Input rejected by the native sequence helpers.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ThalError {
+        /// No bases left after trimming.
+        EmptySequence,
+        /// A byte outside ACGT, with its 0-based position in the input.
+        InvalidBase { base: char, position: usize },
+        /// An alignment gap (`-` or `.`) at this 0-based position; gapped
+        /// input needs `score_alignment`, not a perfect-duplex model.
+        GapInSequence { position: usize },
+    }
+
+    impl std::fmt::Display for ThalError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                ThalError::EmptySequence => write!(f, "empty sequence"),
+                ThalError::InvalidBase { base, position } => {
+                    write!(f, "invalid base {:?} at position {}", base, position)
+                }
+                ThalError::GapInSequence { position } => {
+                    write!(f, "gap at position {} (use score_alignment for aligned input)", position)
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for ThalError {}
+
+    /// Clean a user-supplied sequence for scoring: whitespace anywhere is
+    /// dropped, bases are uppercased, and with `strip_gaps` alignment gaps
+    /// (`-`, `.`) are removed. Anything else outside ACGT is an error, so
+    /// the result is ready for `calculate_thermo`.
+    pub fn normalize_sequence(input: &str, strip_gaps: bool) -> Result<Vec<u8>, ThalError> {
+        let mut seq = Vec::with_capacity(input.len());
+        for (position, c) in input.chars().enumerate() {
+            if c.is_whitespace() || (strip_gaps && matches!(c, '-' | '.')) {
+                continue;
+            }
+            match c.to_ascii_uppercase() {
+                base @ ('A' | 'C' | 'G' | 'T') => seq.push(base as u8),
+                '-' | '.' => return Err(ThalError::GapInSequence { position }),
+                _ => return Err(ThalError::InvalidBase { base: c, position }),
+            }
+        }
+        if seq.is_empty() {
+            return Err(ThalError::EmptySequence);
+        }
+        Ok(seq)
+    }
+
+    /// Check that `seq` is uppercase ACGT only, naming the first offending
+    /// byte; gaps get their own error so aligned input is not mistaken for
+    /// a typo.
+    pub fn validate_bases(seq: &[u8]) -> Result<(), ThalError> {
+        match seq.iter().position(|&b| complement(b).is_none()) {
+            None => Ok(()),
+            Some(position) if is_gap(seq[position]) => Err(ThalError::GapInSequence { position }),
+            Some(position) => Err(ThalError::InvalidBase { base: seq[position] as char, position }),
+        }
+    }
+
+    /// Watson-Crick complement of an uppercase DNA base, or `None` for any
+    /// byte outside ACGT.
+    pub fn complement(base: u8) -> Option<u8> {
+        match base {
+            b'A' => Some(b'T'),
+            b'T' => Some(b'A'),
+            b'C' => Some(b'G'),
+            b'G' => Some(b'C'),
+            _ => None,
+        }
+    }
+
+    /// Canonical bases an uppercase IUPAC code stands for; ACGT map to
+    /// themselves and non-IUPAC bytes give `None`.
+    pub fn iupac_bases(code: u8) -> Option<&'static [u8]> {
+        Some(match code {
+            b'A' => b"A",
+            b'C' => b"C",
+            b'G' => b"G",
+            b'T' | b'U' => b"T",
+            b'R' => b"AG",
+            b'Y' => b"CT",
+            b'S' => b"CG",
+            b'W' => b"AT",
+            b'K' => b"GT",
+            b'M' => b"AC",
+            b'B' => b"CGT",
+            b'D' => b"AGT",
+            b'H' => b"ACT",
+            b'V' => b"ACG",
+            b'N' => b"ACGT",
+            _ => return None,
+        })
+    }
+
+    /// Reverse complement of `seq`, IUPAC-aware and case-preserving, so
+    /// soft-masked (lowercase) bases stay masked on the opposite strand.
+    /// Bytes without a complement are copied through unchanged.
+    pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+        seq.iter()
+            .rev()
+            .map(|&b| {
+                let c = match b.to_ascii_uppercase() {
+                    b'A' => b'T',
+                    b'T' | b'U' => b'A',
+                    b'C' => b'G',
+                    b'G' => b'C',
+                    b'R' => b'Y',
+                    b'Y' => b'R',
+                    b'K' => b'M',
+                    b'M' => b'K',
+                    b'B' => b'V',
+                    b'V' => b'B',
+                    b'D' => b'H',
+                    b'H' => b'D',
+                    _ => return b,
+                };
+                if b.is_ascii_lowercase() { c.to_ascii_lowercase() } else { c }
+            })
+            .collect()
+    }
+
+    /// The lexicographically smaller of `seq` and its reverse complement,
+    /// so both strands of a site reduce to one representation.
+    pub fn canonical_kmer(seq: &[u8]) -> Vec<u8> {
+        let rc = reverse_complement(seq);
+        if rc.as_slice() < seq { rc } else { seq.to_vec() }
+    }
+
+    /// Watson-Crick pairs formed when `b` lies antiparallel to `a`, `a`'s
+    /// 5' end opposite `b`'s 3' end; unequal lengths pair over the shorter.
+    /// Case-insensitive, and non-ACGT bases never pair. A perfect duplex
+    /// `match_score(s, &reverse_complement(s))` scores `s.len()`.
+    pub fn match_score(a: &[u8], b: &[u8]) -> usize {
+        a.iter()
+            .zip(b.iter().rev())
+            .filter(|(&x, &y)| complement(x.to_ascii_uppercase()).is_some_and(|c| c == y.to_ascii_uppercase()))
+            .count()
+    }
+
+    /// Whether `seq` equals its own reverse complement. Returns `None` when
+    /// `seq` contains a non-ACGT byte, so an invalid base is not mistaken for
+    /// a non-symmetric duplex.
+    pub fn is_self_complementary(seq: &[u8]) -> Option<bool> {
+        let mut symmetric = true;
+        for (&a, &b) in seq.iter().zip(seq.iter().rev()) {
+            if complement(a)? != b {
+                symmetric = false;
+            }
+        }
+        Some(symmetric)
+    }
+
+    /// The ten unique Watson-Crick stacks, each listed by its 5'->3' step
+    /// on the top strand (the other six steps are their reverse complements).
+    pub const NN_STACKS: [[u8; 2]; 10] = [
+        *b"AA", *b"AT", *b"TA", *b"CA", *b"GT", *b"CT", *b"GA", *b"CG", *b"GC", *b"GG",
+    ];
+
+    /// SantaLucia 1998 unified nearest-neighbor stack for the 5'->3' step
+    /// `a``b`: (ΔH kcal/mol, ΔS cal/K/mol). `None` outside ACGT.
+    pub fn nn_params(a: u8, b: u8) -> Option<(f64, f64)> {
+        match (a, b) {
+            (b'A', b'A') | (b'T', b'T') => Some((-7.9, -22.2)),
+            (b'A', b'T') => Some((-7.2, -20.4)),
+            (b'T', b'A') => Some((-7.2, -21.3)),
+            (b'C', b'A') | (b'T', b'G') => Some((-8.5, -22.7)),
+            (b'G', b'T') | (b'A', b'C') => Some((-8.4, -22.4)),
+            (b'C', b'T') | (b'A', b'G') => Some((-7.8, -21.0)),
+            (b'G', b'A') | (b'T', b'C') => Some((-8.2, -22.2)),
+            (b'C', b'G') => Some((-10.6, -27.2)),
+            (b'G', b'C') => Some((-9.8, -24.4)),
+            (b'C', b'C') | (b'G', b'G') => Some((-8.0, -19.9)),
+            _ => None,
+        }
+    }
+
+    /// Count of each `NN_STACKS` entry among the dinucleotide steps of
+    /// `seq`, in `NN_STACKS` order; a step is tallied under itself or its
+    /// reverse complement (`TT` counts as `AA`). Case-insensitive, and steps
+    /// touching a non-ACGT base are skipped, so the counts sum to at most
+    /// `seq.len() - 1`.
+    pub fn stack_histogram(seq: &[u8]) -> [usize; 10] {
+        let mut counts = [0; 10];
+        for step in seq.windows(2) {
+            let step = [step[0].to_ascii_uppercase(), step[1].to_ascii_uppercase()];
+            let (Some(c0), Some(c1)) = (complement(step[0]), complement(step[1])) else { continue };
+            let rc = [c1, c0];
+            if let Some(i) = NN_STACKS.iter().position(|s| *s == step || *s == rc) {
+                counts[i] += 1;
+            }
+        }
+        counts
+    }
+
+    /// `nn_params` indexed by the 2-bit codes of a step's bases
+    /// (`4 * code(a) + code(b)`), ΔH and ΔS in separate rows so each loads
+    /// straight into SIMD lanes.
+    struct StackTable {
+        dh: [f64; 16],
+        ds: [f64; 16],
+    }
+
+    fn stack_table() -> &'static StackTable {
+        static TABLE: std::sync::OnceLock<StackTable> = std::sync::OnceLock::new();
+        TABLE.get_or_init(|| {
+            let mut table = StackTable { dh: [0.0; 16], ds: [0.0; 16] };
+            for i in 0..16 {
+                let (h, s) = nn_params(b"ACGT"[i / 4], b"ACGT"[i % 4]).expect("ACGT steps have parameters");
+                table.dh[i] = h;
+                table.ds[i] = s;
+            }
+            table
+        })
+    }
+
+    /// 2-bit code of each byte: A, C, G, T = 0-3, anything else 4.
+    const BASE_CODE: [u8; 256] = {
+        let mut code = [4u8; 256];
+        code[b'A' as usize] = 0;
+        code[b'C' as usize] = 1;
+        code[b'G' as usize] = 2;
+        code[b'T' as usize] = 3;
+        code
+    };
+
+    /// Sum of `nn_params` over the dinucleotide steps of `seq`: stacked ΔH
+    /// (kcal/mol) and ΔS (cal/K/mol) only, no initiation, symmetry or salt
+    /// terms. `None` if `seq` has a byte outside uppercase ACGT. With the
+    /// `simd` feature four steps are summed per lane group; the lanes
+    /// reassociate the sum, so the two paths agree to rounding, not bit for
+    /// bit.
+    pub fn stack_sums(seq: &[u8]) -> Option<(f64, f64)> {
+        if seq.iter().any(|&b| BASE_CODE[b as usize] > 3) {
+            return None;
+        }
+        let table = stack_table();
+        let step = |i: usize| (4 * BASE_CODE[seq[i] as usize] + BASE_CODE[seq[i + 1] as usize]) as usize;
+        let steps = seq.len().saturating_sub(1);
+        #[cfg(not(feature = "simd"))]
+        let (mut dh, mut ds, mut i) = (0.0, 0.0, 0);
+        #[cfg(feature = "simd")]
+        let (mut dh, mut ds, mut i) = {
+            use wide::f64x4;
+            let (mut dh_lanes, mut ds_lanes, mut i) = (f64x4::ZERO, f64x4::ZERO, 0);
+            while i + 4 <= steps {
+                let idx = [step(i), step(i + 1), step(i + 2), step(i + 3)];
+                dh_lanes += f64x4::from(idx.map(|j| table.dh[j]));
+                ds_lanes += f64x4::from(idx.map(|j| table.ds[j]));
+                i += 4;
+            }
+            (dh_lanes.to_array().iter().sum::<f64>(), ds_lanes.to_array().iter().sum::<f64>(), i)
+        };
+        // Scalar path, and the SIMD remainder
+        while i < steps {
+            let j = step(i);
+            dh += table.dh[j];
+            ds += table.ds[j];
+            i += 1;
+        }
+        Some((dh, ds))
+    }
+
+    /// Initiation for one terminal base pair (SantaLucia 1998):
+    /// (ΔH kcal/mol, ΔS cal/K/mol).
+    pub fn terminal_init(base: u8) -> (f64, f64) {
+        match base {
+            b'G' | b'C' => (0.1, -2.8),
+            _ => (2.3, 4.1),
+        }
+    }
+
+    /// An unscorable result: `msg` set, `temp == THAL_ERROR_SCORE` and zero
+    /// energies, as `calculate_thermo` returns for bad input.
+    pub fn error_results(msg: &str) -> ThalResults {
+        ThalResults {
+            msg: msg.to_string(),
+            temp: THAL_ERROR_SCORE,
+            dg: 0.0,
+            ds: 0.0,
+            dh: 0.0,
+            align_end_1: 0,
+            align_end_2: 0,
+            sec_struct: None,
+        }
+    }
+
+    /// Nearest-neighbor thermodynamics of `seq` paired with its perfect
+    /// complement, salt-corrected per `args.salt_method`. Units follow `thal`:
+    /// `dg`/`dh` in cal/mol, `ds` in cal/K/mol, `temp` is the Tm in °C.
+    /// Sequences shorter than 2 bases or containing non-ACGT bytes (gaps
+    /// included, see `validate_bases`) return a non-empty `msg` and
+    /// `temp == THAL_ERROR_SCORE`.
+    pub fn calculate_thermo(seq: &[u8], args: &ThalArgs) -> ThalResults {
+        if seq.len() < 2 {
+            return error_results("Sequence too short");
+        }
+        if let Err(e) = validate_bases(seq) {
+            return error_results(&e.to_string());
+        }
+        let symmetric = scored_as_symmetric(seq, args);
+
+        let (h5, s5) = terminal_init(seq[0]);
+        let (h3, s3) = terminal_init(seq[seq.len() - 1]);
+        let (stack_dh, stack_ds) = stack_sums(seq).expect("validated by validate_bases");
+        let dh = h5 + h3 + stack_dh;
+        let ds = s5 + s3 + stack_ds;
+
+        finish_duplex(dh, ds, seq.len(), gc_fraction(seq), symmetric, args)
+    }
+
+    /// Whether the native model treats `seq`'s duplex as self-complementary:
+    /// it is, and `args.symmetry_correction` is on.
+    fn scored_as_symmetric(seq: &[u8], args: &ThalArgs) -> bool {
+        args.symmetry_correction && is_self_complementary(seq) == Some(true)
+    }
+
+    /// Monovalent-equivalent salt (mM) used by the native model: free Mg2+
+    /// (Mg2+ minus dNTP, floored at zero) counts as 120·sqrt([Mg2+]) Na+.
+    pub fn na_equivalent(args: &ThalArgs) -> f64 {
+        monovalent_equivalent(args.mv, args.dv, args.dntp)
+    }
+
+    fn monovalent_equivalent(mv: f64, dv: f64, dntp: f64) -> f64 {
+        mv + DIVALENT_COEFF * (dv - dntp).max(0.0).sqrt()
+    }
+
+    /// The SantaLucia 1998 salt term alone: the ΔS (cal/K/mol) added to a
+    /// `seq_len`-bp duplex, 0.368·(N-1)·ln([Na+eq] / 1 M), with `mv`, `dv`
+    /// and `dntp` in mM as in `ThalArgs`. Zero at 1 M Na+ and negative
+    /// below it.
+    pub fn salt_correction_ds(seq_len: usize, mv: f64, dv: f64, dntp: f64) -> f64 {
+        SALT_ENTROPY_COEFF * (seq_len as f64 - 1.0) * (monovalent_equivalent(mv, dv, dntp) / 1000.0).ln()
+    }
+
+    /// ln(C_T / x) for the two-state duplex equilibrium: x = 1 for a
+    /// self-complementary duplex, 4 for equimolar non-self-complementary
+    /// strands.
+    fn concentration_term(symmetric: bool, args: &ThalArgs) -> f64 {
+        let c_factor = if symmetric { 1.0 } else { 4.0 };
+        (args.dna_conc / 1e9 / c_factor).ln()
+    }
+
+    /// Shared tail of the native NN model: symmetry and salt corrections,
+    /// then Tm and ΔG at `args.temp`. `dh` is kcal/mol, `ds` cal/K/mol,
+    /// `n_bp` the number of base pairs in the duplex and `gc` their GC
+    /// fraction. Methods that correct the Tm rather than ΔS (Schildkraut,
+    /// Owczarzy) report the ΔS that reproduces that Tm, so `dg` and `temp`
+    /// stay consistent.
+    fn finish_duplex(dh: f64, mut ds: f64, n_bp: usize, gc: f64, symmetric: bool, args: &ThalArgs) -> ThalResults {
+        // Symmetry correction for self-complementary duplexes (SantaLucia
+        // 1998): ΔS -1.4 cal/K/mol, i.e. ΔG37 +0.43 kcal/mol
+        if symmetric {
+            ds += -1.4;
+        }
+
+        // Mg2+ enters every method as Na+ equivalents
+        let ln_na = (na_equivalent(args) / 1000.0).ln();
+        let dh_cal = dh * CAL_PER_KCAL;
+        let c_term = R_CAL * concentration_term(symmetric, args);
+        let tm_kelvin = match args.salt_method {
+            SaltMethod::Santalucia => {
+                ds += salt_correction_ds(n_bp, args.mv, args.dv, args.dntp);
+                dh_cal / (ds + c_term)
+            }
+            SaltMethod::Schildkraut => dh_cal / (ds + c_term) + 16.6 * ln_na / std::f64::consts::LN_10,
+            SaltMethod::Owczarzy => {
+                let tm_1m = dh_cal / (ds + c_term);
+                1.0 / (1.0 / tm_1m + (4.29 * gc - 3.95) * 1e-5 * ln_na + 9.40e-6 * ln_na * ln_na)
+            }
+        };
+        if args.salt_method != SaltMethod::Santalucia {
+            ds = dh_cal / tm_kelvin - c_term;
+        }
+        let tm = tm_kelvin - ABSOLUTE_ZERO;
+
+        ThalResults {
+            msg: String::new(),
+            temp: tm,
+            dg: dh_cal - args.temp.as_kelvin() * ds,
+            ds,
+            dh: dh_cal,
+            align_end_1: n_bp as i32,
+            align_end_2: n_bp as i32,
+            sec_struct: None,
+        }
+    }
+
+    /// Tm (°C) of a non-self-complementary `seq_len`-bp duplex from
+    /// externally measured 1 M Na+ thermodynamics, `dh` in kcal/mol and `ds`
+    /// in cal/K/mol, with `args`' salt correction and strand concentration
+    /// applied as for the NN sum. Owczarzy's GC-dependent term assumes 50% GC.
+    pub fn tm_from_enthalpy_entropy(dh: f64, ds: f64, seq_len: usize, args: &ThalArgs) -> f64 {
+        finish_duplex(dh, ds, seq_len, 0.5, false, args).temp
+    }
+
+    /// Bulge loop ΔG37 (kcal/mol) for 1-10 unpaired bases on one strand
+    /// (SantaLucia & Hicks 2004).
+    const BULGE_LOOP_DG: [f64; 10] = [4.0, 2.9, 3.1, 3.2, 3.3, 3.5, 3.7, 3.9, 4.1, 4.3];
+    /// Internal loop ΔG37 (kcal/mol) for 1-10 unpaired bases in total
+    /// (SantaLucia & Hicks 2004; sizes below 3 are clamped to the 3 value).
+    const INTERNAL_LOOP_DG: [f64; 10] = [3.2, 3.2, 3.2, 3.6, 4.0, 4.4, 4.6, 4.8, 4.9, 4.9];
+
+    /// Look up a loop ΔG37 by size, extrapolating past the table with the
+    /// Jacobson-Stockmayer term 2.44·RT·ln(n/n_max).
+    fn loop_dg(table: &[f64], n: usize) -> f64 {
+        let n_max = table.len();
+        if n <= n_max {
+            table[n.max(1) - 1]
+        } else {
+            table[n_max - 1] + 2.44 * R_CAL * (37.0 + KELVIN_OFFSET) * (n as f64 / n_max as f64).ln() / CAL_PER_KCAL
+        }
+    }
+
+    /// Loop penalties are purely entropic in the NN model, so convert a
+    /// ΔG37 (kcal/mol) into ΔS (cal/K/mol).
+    fn loop_ds(dg37: f64) -> f64 {
+        -dg37 * CAL_PER_KCAL / (37.0 + KELVIN_OFFSET)
+    }
+
+    /// Hairpin loop ΔG37 (kcal/mol) for 1-9 unpaired bases (SantaLucia &
+    /// Hicks 2004; loops under 3 cannot close and are clamped to the 3 value).
+    const HAIRPIN_LOOP_DG: [f64; 9] = [3.5, 3.5, 3.5, 3.5, 3.3, 4.0, 4.2, 4.3, 4.5];
+    /// Fewest unpaired bases a hairpin loop can close around.
+    const MIN_HAIRPIN_LOOP: usize = 3;
+    /// Linear multibranch loop ΔG37 (kcal/mol): a flat cost per loop plus
+    /// one per branch, unpaired bases free (Mathews et al. 1999).
+    const MULTILOOP_INIT_DG: f64 = 3.4;
+    const MULTILOOP_BRANCH_DG: f64 = 0.4;
+    /// Longest sequence `mfe_fold` folds; the DP is cubic in length.
+    pub const MFE_MAX_LEN: usize = 100;
+
+    /// How the MFE of a closed pair (i, j) was reached.
+    #[derive(Clone, Copy)]
+    enum PairFrom {
+        Hairpin,
+        Interior(usize, usize),
+        Multi(usize),
+    }
+
+    /// How the MFE of a multiloop segment was reached.
+    #[derive(Clone, Copy)]
+    enum SegmentFrom {
+        Branch,
+        SkipFirst,
+        SkipLast,
+        Split(usize),
+    }
+
+    /// Minimum free energy (cal/mol at `args.temp`) of `seq` folded on
+    /// itself, with its nested structure in dot-bracket notation: a Zuker
+    /// fold over the native NN stacks, the hairpin, bulge and internal loop
+    /// tables (interior loops up to `args.max_loop` unpaired bases) and a
+    /// linear multiloop. Only Watson-Crick pairs form, salt enters each
+    /// stack as in `salt_correction_ds`, and single mismatches cost nothing
+    /// as in `score_alignment`. An unfolded strand gives 0.0 and all dots;
+    /// a sequence longer than `MFE_MAX_LEN` gives NaN and an empty string.
+    pub fn mfe_fold(seq: &[u8], args: &ThalArgs) -> (f64, String) {
+        let n = seq.len();
+        if n > MFE_MAX_LEN {
+            return (f64::NAN, String::new());
+        }
+        let seq = seq.to_ascii_uppercase();
+        let t = args.temp.as_kelvin();
+        let salt_ds = salt_correction_ds(2, args.mv, args.dv, args.dntp);
+        let pairs = |i: usize, j: usize| complement(seq[i]).is_some_and(|c| c == seq[j]);
+        // Loop terms are entropic, so scale ΔG37 to the working temperature
+        let entropic = |dg37: f64| -t * loop_ds(dg37);
+        let stack = |i: usize, k: usize| {
+            let (h, s) = nn_params(seq[i], seq[k]).unwrap_or((0.0, 0.0));
+            h * CAL_PER_KCAL - t * (s + salt_ds)
+        };
+        let interior = |i: usize, j: usize, k: usize, l: usize| {
+            let (left, right) = (k - i - 1, j - l - 1);
+            match (left, right) {
+                (0, 0) => stack(i, k),
+                (1, 1) => 0.0,
+                (0, b) | (b, 0) if b == 1 => entropic(loop_dg(&BULGE_LOOP_DG, 1)) + stack(i, k),
+                (0, b) | (b, 0) => entropic(loop_dg(&BULGE_LOOP_DG, b)),
+                _ => entropic(loop_dg(&INTERNAL_LOOP_DG, left + right)),
+            }
+        };
+        let max_loop = args.max_loop.max(0) as usize;
+
+        // v: best with i and j paired; wm: best multiloop segment [i, j]
+        // holding at least one branch
+        let mut v = vec![vec![f64::INFINITY; n]; n];
+        let mut v_from = vec![vec![PairFrom::Hairpin; n]; n];
+        let mut wm = vec![vec![f64::INFINITY; n]; n];
+        let mut wm_from = vec![vec![SegmentFrom::Branch; n]; n];
+        for span in MIN_HAIRPIN_LOOP + 1..n {
+            for i in 0..n - span {
+                let j = i + span;
+                if pairs(i, j) {
+                    let mut best = entropic(loop_dg(&HAIRPIN_LOOP_DG, span - 1));
+                    let mut from = PairFrom::Hairpin;
+                    for (k, inner) in v.iter().enumerate().take(j).skip(i + 1) {
+                        if k - i - 1 > max_loop {
+                            break;
+                        }
+                        for l in (k + MIN_HAIRPIN_LOOP + 1..j).rev() {
+                            if (k - i - 1) + (j - l - 1) > max_loop {
+                                break;
+                            }
+                            if inner[l].is_finite() {
+                                let e = inner[l] + interior(i, j, k, l);
+                                if e < best {
+                                    best = e;
+                                    from = PairFrom::Interior(k, l);
+                                }
+                            }
+                        }
+                    }
+                    for u in i + 1..j - 1 {
+                        let e = wm[i + 1][u] + wm[u + 1][j - 1] + entropic(MULTILOOP_INIT_DG + MULTILOOP_BRANCH_DG);
+                        if e < best {
+                            best = e;
+                            from = PairFrom::Multi(u);
+                        }
+                    }
+                    v[i][j] = best;
+                    v_from[i][j] = from;
+                }
+
+                let mut best = v[i][j] + entropic(MULTILOOP_BRANCH_DG);
+                let mut from = SegmentFrom::Branch;
+                for (e, f) in [(wm[i + 1][j], SegmentFrom::SkipFirst), (wm[i][j - 1], SegmentFrom::SkipLast)] {
+                    if e < best {
+                        best = e;
+                        from = f;
+                    }
+                }
+                for k in i..j {
+                    let e = wm[i][k] + wm[k + 1][j];
+                    if e < best {
+                        best = e;
+                        from = SegmentFrom::Split(k);
+                    }
+                }
+                wm[i][j] = best;
+                wm_from[i][j] = from;
+            }
+        }
+
+        // w[j]: best exterior fold of the first j bases, ending either
+        // unpaired or in the pair (w_from[j], j - 1)
+        let mut w = vec![0.0; n + 1];
+        let mut w_from = vec![None; n + 1];
+        for j in 1..=n {
+            w[j] = w[j - 1];
+            for i in 0..j - 1 {
+                let e = w[i] + v[i][j - 1];
+                if e < w[j] {
+                    w[j] = e;
+                    w_from[j] = Some(i);
+                }
+            }
+        }
+
+        let mut structure = vec![b'.'; n];
+        let mut pending = Vec::new();
+        let mut j = n;
+        while j > 0 {
+            match w_from[j] {
+                Some(i) => {
+                    pending.push((i, j - 1, true));
+                    j = i;
+                }
+                None => j -= 1,
+            }
+        }
+        // (i, j, true) is a closed pair, (i, j, false) a multiloop segment
+        while let Some((i, j, paired)) = pending.pop() {
+            if paired {
+                structure[i] = b'(';
+                structure[j] = b')';
+                match v_from[i][j] {
+                    PairFrom::Hairpin => {}
+                    PairFrom::Interior(k, l) => pending.push((k, l, true)),
+                    PairFrom::Multi(u) => pending.extend([(i + 1, u, false), (u + 1, j - 1, false)]),
+                }
+            } else {
+                match wm_from[i][j] {
+                    SegmentFrom::Branch => pending.push((i, j, true)),
+                    SegmentFrom::SkipFirst => pending.push((i + 1, j, false)),
+                    SegmentFrom::SkipLast => pending.push((i, j - 1, false)),
+                    SegmentFrom::Split(k) => pending.extend([(i, k, false), (k + 1, j, false)]),
+                }
+            }
+        }
+        (w[n], String::from_utf8(structure).expect("dot-bracket is ASCII"))
+    }
+
+    /// Alignment gap character (`-` or `.`).
+    pub fn is_gap(b: u8) -> bool {
+        matches!(b, b'-' | b'.')
+    }
+
+    /// Score a pre-computed alignment without re-aligning. `top` is written
+    /// 5'->3' and `bottom` 3'->5' beneath it, column for column, with `-` or
+    /// `.` marking gaps. The duplex spans the first to the last Watson-Crick
+    /// column; adjacent WC columns add NN stacks, a lone mismatch flanked by
+    /// WC pairs contributes no stacking energy (a coarse stand-in for the
+    /// Allawi-SantaLucia mismatch table), and any other run of unpaired
+    /// columns is scored as a bulge (one strand only) or internal loop.
+    /// `align_end_1`/`align_end_2` give the last paired base (1-based) of
+    /// each strand.
+    pub fn score_alignment(top: &[u8], bottom: &[u8], args: &ThalArgs) -> ThalResults {
+        if top.len() != bottom.len() {
+            return error_results("Alignment strands differ in length");
+        }
+        let top = top.to_ascii_uppercase();
+        let bottom = bottom.to_ascii_uppercase();
+        let is_wc = |i: usize| complement(top[i]).is_some_and(|c| c == bottom[i]);
+
+        let wc_cols: Vec<usize> = (0..top.len()).filter(|&i| is_wc(i)).collect();
+        if wc_cols.len() < 2 {
+            return error_results("Alignment has fewer than 2 base pairs");
+        }
+        let (first, last) = (wc_cols[0], wc_cols[wc_cols.len() - 1]);
+
+        let (h5, s5) = terminal_init(top[first]);
+        let (h3, s3) = terminal_init(top[last]);
+        let mut dh = h5 + h3;
+        let mut ds = s5 + s3;
+
+        for pair in wc_cols.windows(2) {
+            let (l, r) = (pair[0], pair[1]);
+            if r == l + 1 {
+                let (h, s) = nn_params(top[l], top[r]).unwrap_or((0.0, 0.0));
+                dh += h;
+                ds += s;
+                continue;
+            }
+            let inner = l + 1..r;
+            let n_top = inner.clone().filter(|&i| !is_gap(top[i])).count();
+            let n_bottom = inner.clone().filter(|&i| !is_gap(bottom[i])).count();
+            let has_gap = inner.clone().any(|i| is_gap(top[i]) || is_gap(bottom[i]));
+            if !has_gap && r == l + 2 {
+                // Single internal mismatch: both flanking stacks contribute 0
+                continue;
+            }
+            if n_top == 0 || n_bottom == 0 {
+                let n = n_top.max(n_bottom);
+                ds += loop_ds(loop_dg(&BULGE_LOOP_DG, n));
+                if n == 1 {
+                    // A single-base bulge keeps the stack across it
+                    let (h, s) = nn_params(top[l], top[r]).unwrap_or((0.0, 0.0));
+                    dh += h;
+                    ds += s;
+                }
+            } else {
+                ds += loop_ds(loop_dg(&INTERNAL_LOOP_DG, n_top + n_bottom));
+            }
+        }
+
+        let gc = gc_fraction(&wc_cols.iter().map(|&i| top[i]).collect::<Vec<u8>>());
+        let mut results = finish_duplex(dh, ds, wc_cols.len(), gc, false, args);
+        results.align_end_1 = top[..=last].iter().filter(|&&b| !is_gap(b)).count() as i32;
+        results.align_end_2 = bottom[..=last].iter().filter(|&&b| !is_gap(b)).count() as i32;
+        results
+    }
+
+    /// Native NN thermodynamics of `primer` bound to a genomic `target`
+    /// written in the primer's sense (identical to it on a perfect match),
+    /// aligned base for base without gaps. Matching columns add WC stacks
+    /// and mismatches are scored as in `score_alignment`, so the Tm reflects
+    /// the imperfect duplex; non-ACGT target bases never pair.
+    pub fn calculate_thermo_against(primer: &[u8], target: &[u8], args: &ThalArgs) -> ThalResults {
+        let bottom: Vec<u8> = target
+            .iter()
+            .map(|&b| complement(b.to_ascii_uppercase()).unwrap_or(b'N'))
+            .collect();
+        score_alignment(primer, &bottom, args)
+    }
+
+    /// Match bars for `primer` written over a `target` in the primer's sense,
+    /// as `calculate_thermo_against` pairs them: `|` where the target base is
+    /// the same ACGT base as the primer's, a space elsewhere.
+    pub fn match_bars(primer: &[u8], target: &[u8]) -> String {
+        primer
+            .iter()
+            .zip(target)
+            .map(|(p, t)| {
+                let (p, t) = (p.to_ascii_uppercase(), t.to_ascii_uppercase());
+                if p == t && complement(p).is_some() { '|' } else { ' ' }
+            })
+            .collect()
+    }
+
+    /// ΔG (cal/mol) of the duplex formed by the last `n` bases of `seq`, the
+    /// primer3 3' end stability measure (primer3 uses `n = 5`). Returns 0.0
+    /// if the tail cannot be scored.
+    pub fn three_prime_stability(seq: &[u8], n: usize, args: &ThalArgs) -> f64 {
+        let tail = &seq[seq.len().saturating_sub(n)..];
+        let result = calculate_thermo(tail, args);
+        if result.msg.is_empty() { result.dg } else { 0.0 }
+    }
+
+    /// ΔG (cal/mol) of the 5' and 3' halves of `seq`, each scored as its
+    /// own perfect duplex; an odd middle base goes to the 3' half. The
+    /// halves need not sum to the whole: the junction step is dropped and
+    /// each half pays initiation. A half that cannot be scored gives 0.0.
+    pub fn half_stability(seq: &[u8], args: &ThalArgs) -> (f64, f64) {
+        let (five, three) = seq.split_at(seq.len() / 2);
+        let dg = |half: &[u8]| {
+            let result = calculate_thermo(half, args);
+            if result.msg.is_empty() { result.dg } else { 0.0 }
+        };
+        (dg(five), dg(three))
+    }
+
+    /// Effective ΔG (cal/mol) of duplex formation at `temp` and the
+    /// working strand concentration: ΔG° − RT·ln(C_T/x), with the same x
+    /// (1 self-complementary, 4 otherwise) as the Tm. Unlike the
+    /// standard-state `calculate_thermo(..).dg` (1 M strands), this crosses
+    /// zero exactly at the Tm and is negative below it. NaN if `seq` cannot
+    /// be scored.
+    pub fn delta_g_effective(seq: &[u8], args: &ThalArgs, temp: Temperature) -> f64 {
+        let result = calculate_thermo(seq, args);
+        if !result.msg.is_empty() {
+            return f64::NAN;
+        }
+        let symmetric = scored_as_symmetric(seq, args);
+        let t = temp.as_kelvin();
+        result.dh - t * result.ds - R_CAL * t * concentration_term(symmetric, args)
+    }
+
+    /// Lazily enumerate the ACGT `k`-mers, in lexicographic order, whose
+    /// perfect-match duplex has `dg <= threshold` (cal/mol, as returned by
+    /// `calculate_thermo`). Nothing is materialised, but the scan still visits
+    /// all 4^k candidates; `k` outside 2..=31 yields nothing.
+    pub fn stable_kmers_iter(k: usize, args: &ThalArgs, threshold: f64) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let count = if (2..=31).contains(&k) { 1u64 << (2 * k) } else { 0 };
+        let bounds = DgBounds::new(k, args);
+        (0..count)
+            .map(move |index| {
+                (0..k).map(|i| b"ACGT"[((index >> (2 * (k - 1 - i))) & 3) as usize]).collect::<Vec<u8>>()
+            })
+            .filter(move |kmer| {
+                let gc = kmer.iter().filter(|&&b| matches!(b, b'G' | b'C')).count();
+                if !bounds.can_pass(gc, threshold) {
+                    return false;
+                }
+                let result = calculate_thermo(kmer, args);
+                result.msg.is_empty() && result.dg <= threshold
+            })
+    }
+
+    /// Range of native-model ΔG (cal/mol, as `calculate_thermo(..).dg`) a
+    /// `len`-bp perfect duplex can take given only its G/C count, for
+    /// discarding windows before the full NN sum. Stacks are grouped by how
+    /// many G/C bases they contain and each group is bounded by its most
+    /// and least stable member, so the range always contains the true ΔG.
+    /// Only the SantaLucia salt correction keeps ΔS additive; other
+    /// methods get unbounded ranges.
+    #[derive(Debug, Clone)]
+    pub struct DgBounds {
+        lower: Vec<f64>,
+        upper: Vec<f64>,
+    }
+
+    impl DgBounds {
+        pub fn new(len: usize, args: &ThalArgs) -> Self {
+            if len < 2 || args.salt_method != SaltMethod::Santalucia {
+                return DgBounds {
+                    lower: vec![f64::NEG_INFINITY; len + 1],
+                    upper: vec![f64::INFINITY; len + 1],
+                };
+            }
+            let t = args.temp.as_kelvin();
+            let dg = |(h, s): (f64, f64)| h * CAL_PER_KCAL - t * s;
+            // Per-stack ΔG range by number of G/C bases in the stack
+            let mut stack_min = [f64::INFINITY; 3];
+            let mut stack_max = [f64::NEG_INFINITY; 3];
+            for [a, b] in NN_STACKS {
+                let k = [a, b].iter().filter(|&&x| matches!(x, b'G' | b'C')).count();
+                let v = dg(nn_params(a, b).expect("NN_STACKS are canonical"));
+                stack_min[k] = stack_min[k].min(v);
+                stack_max[k] = stack_max[k].max(v);
+            }
+            let (init_gc, init_at) = (dg(terminal_init(b'G')), dg(terminal_init(b'A')));
+            let salt = -t * salt_correction_ds(len, args.mv, args.dv, args.dntp);
+            let symmetry = t * 1.4;
+
+            let steps = len - 1;
+            let mut lower = vec![f64::INFINITY; len + 1];
+            let mut upper = vec![f64::NEG_INFINITY; len + 1];
+            for gc in 0..=len {
+                // `ends` G/C bases at the termini; every interior base sits
+                // in two stacks, each terminus in one
+                for ends in 0..=2usize.min(gc) {
+                    if 2 - ends > len - gc {
+                        continue;
+                    }
+                    let init = ends as f64 * init_gc + (2 - ends) as f64 * init_at;
+                    let in_stacks = 2 * gc - ends;
+                    for x2 in 0..=steps.min(in_stacks / 2) {
+                        let x1 = in_stacks - 2 * x2;
+                        if x1 + x2 > steps {
+                            continue;
+                        }
+                        let x = [steps - x1 - x2, x1, x2];
+                        let lo: f64 = (0..3).map(|k| x[k] as f64 * stack_min[k]).sum();
+                        let hi: f64 = (0..3).map(|k| x[k] as f64 * stack_max[k]).sum();
+                        lower[gc] = lower[gc].min(init + lo + salt);
+                        upper[gc] = upper[gc].max(init + hi + salt + symmetry);
+                    }
+                }
+            }
+            DgBounds { lower, upper }
+        }
+
+        /// Least ΔG any duplex with `gc` G/C bases can have.
+        pub fn lower(&self, gc: usize) -> f64 {
+            self.lower[gc]
         }
+
+        /// Greatest ΔG any duplex with `gc` G/C bases can have.
+        pub fn upper(&self, gc: usize) -> f64 {
+            self.upper[gc]
+        }
+
+        /// False only when no duplex with `gc` G/C bases can reach
+        /// `dg <= threshold`.
+        pub fn can_pass(&self, gc: usize, threshold: f64) -> bool {
+            self.lower[gc] <= threshold
+        }
+    }
+
+    /// All `k`-mers passing `stable_kmers_iter`, collected. Prefer the
+    /// iterator for large `k`.
+    pub fn stable_kmers(k: usize, args: &ThalArgs, threshold: f64) -> Vec<Vec<u8>> {
+        stable_kmers_iter(k, args, threshold).collect()
+    }
+
+    /// Tm shift (°C) of `seq`'s perfect duplex going from conditions `a` to
+    /// `b`: `Tm(b) - Tm(a)` with the native model, so only the salt, dNTP
+    /// and strand-concentration terms can differ. NaN if `seq` cannot be
+    /// scored.
+    pub fn delta_tm(seq: &[u8], a: &ThalArgs, b: &ThalArgs) -> f64 {
+        let (ra, rb) = (calculate_thermo(seq, a), calculate_thermo(seq, b));
+        if !ra.msg.is_empty() || !rb.msg.is_empty() {
+            return f64::NAN;
+        }
+        rb.temp - ra.temp
+    }
+
+    /// Tm (°C) of the ligated product of `left` joined 5'->3' to `right`:
+    /// a convenience wrapper for `calculate_thermo` on the concatenation,
+    /// so the NN stack spanning the join counts like any other. It models
+    /// the sealed product, not the nicked duplex before ligation (no
+    /// coaxial-stacking or nick terms). NaN if either oligo is empty or
+    /// the product cannot be scored.
+    pub fn ligation_junction_tm(left: &[u8], right: &[u8], args: &ThalArgs) -> f64 {
+        if left.is_empty() || right.is_empty() {
+            return f64::NAN;
+        }
+        let result = calculate_thermo(&[left, right].concat(), args);
+        if result.msg.is_empty() { result.temp } else { f64::NAN }
+    }
+
+    /// Tm (°C) of `seq`'s perfect duplex when its two strands are at unequal
+    /// concentrations, e.g. asymmetric PCR. `ct_total` is the summed strand
+    /// concentration (nM, like `args.dna_conc`) and `ratio` the excess
+    /// strand's concentration over the limiting one's (values below 1 are
+    /// inverted). `ratio = 1` with `ct_total = args.dna_conc` reproduces
+    /// `calculate_thermo(..).temp`. NaN if `seq` cannot be scored or
+    /// `ratio` is not positive.
+    ///
+    /// With limiting strand B at C_B = C_T/(1+r) and excess A at r·C_B, half
+    /// of B is duplexed at the Tm, so K = [AB]/([A][B]) = 1/(C_A - C_B/2)
+    /// and Tm = ΔH / (ΔS + R ln(C_A - C_B/2)). At r = 1 this is C_T/4, the
+    /// usual heterodimer term; a self-complementary duplex keeps C_T. The
+    /// salt-corrected ΔS is taken from `calculate_thermo`, which is exact
+    /// for the additive SantaLucia correction.
+    pub fn tm_asymmetric(seq: &[u8], args: &ThalArgs, ct_total: f64, ratio: f64) -> f64 {
+        let result = calculate_thermo(seq, args);
+        if !result.msg.is_empty() || ratio <= 0.0 {
+            return f64::NAN;
+        }
+        let r = if ratio < 1.0 { 1.0 / ratio } else { ratio };
+        let ct = ct_total / 1e9;
+        let c_eff = if scored_as_symmetric(seq, args) {
+            ct
+        } else {
+            ct * (r - 0.5) / (1.0 + r)
+        };
+        result.dh / (result.ds + R_CAL * c_eff.ln()) - ABSOLUTE_ZERO
+    }
+
+    /// `dg` spread over the `len - 1` nearest-neighbor steps of a `len`-bp
+    /// duplex, for comparing primers of different lengths; in the units of
+    /// `dg`. NaN when `len < 2`.
+    pub fn delta_g_per_bp(dg: f64, len: usize) -> f64 {
+        if len < 2 {
+            return f64::NAN;
+        }
+        dg / (len - 1) as f64
+    }
+
+    /// Oligo length range (nt) the SantaLucia NN parameters were fitted on.
+    pub const TM_RELIABLE_LEN: std::ops::RangeInclusive<usize> = 8..=60;
+    /// GC fraction range outside which NN Tm predictions degrade.
+    pub const TM_RELIABLE_GC: std::ops::RangeInclusive<f64> = 0.2..=0.8;
+    /// Na+ equivalent range (mM) over which the SantaLucia salt correction
+    /// was validated.
+    pub const TM_RELIABLE_NA_EQ: std::ops::RangeInclusive<f64> = 50.0..=1100.0;
+
+    /// Reasons a Tm for `seq` under `args` falls outside the model's
+    /// validity bounds (`TM_RELIABLE_*`). Empty when the prediction is
+    /// within the documented range.
+    pub fn tm_warnings(seq: &[u8], args: &ThalArgs) -> Vec<&'static str> {
+        let mut warnings = Vec::new();
+        if !TM_RELIABLE_LEN.contains(&seq.len()) {
+            warnings.push("length outside 8-60 nt");
+        }
+        if !seq.is_empty() && !TM_RELIABLE_GC.contains(&gc_fraction(seq)) {
+            warnings.push("GC fraction outside 20-80%");
+        }
+        if ambiguous_fraction(seq) > 0.0 {
+            warnings.push("contains non-ACGT bases");
+        }
+        let na_eq = na_equivalent(args);
+        if !TM_RELIABLE_NA_EQ.contains(&na_eq) {
+            warnings.push("Na+ equivalent outside 50-1100 mM");
+        }
+        warnings
+    }
+
+    /// Fraction of G/C bases in `seq` (either case); 0.0 when empty.
+    pub fn gc_fraction(seq: &[u8]) -> f64 {
+        if seq.is_empty() {
+            return 0.0;
+        }
+        let gc = seq.iter().filter(|b| matches!(b.to_ascii_uppercase(), b'G' | b'C')).count();
+        gc as f64 / seq.len() as f64
+    }
+
+    /// Quick check that a Tm for `seq` under `args` is within the model's
+    /// validity bounds; see `tm_warnings` for the reasons when it is not.
+    pub fn tm_is_reliable(seq: &[u8], args: &ThalArgs) -> bool {
+        tm_warnings(seq, args).is_empty()
+    }
+
+    /// Wallace rule Tm (°C): 2·(A+T) + 4·(G+C). Only meaningful for short
+    /// oligos (< ~14 nt); non-ACGT bytes contribute nothing.
+    pub fn wallace_tm(seq: &[u8]) -> f64 {
+        seq.iter()
+            .map(|b| match b {
+                b'A' | b'T' => 2.0,
+                b'G' | b'C' => 4.0,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    /// Fraction of bytes in `seq` that are not A, C, G or T (either case),
+    /// e.g. N runs in draft assemblies. 0.0 for an empty slice.
+    pub fn ambiguous_fraction(seq: &[u8]) -> f64 {
+        if seq.is_empty() {
+            return 0.0;
+        }
+        let ambiguous = seq
+            .iter()
+            .filter(|b| !matches!(b.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T'))
+            .count();
+        ambiguous as f64 / seq.len() as f64
+    }
+
+    /// Number of G/C bases among the last `window` bases of `seq`. A primer
+    /// is usually considered clamped with one or two in its last five.
+    pub fn gc_clamp_count(seq: &[u8], window: usize) -> usize {
+        seq[seq.len().saturating_sub(window)..]
+            .iter()
+            .filter(|&&b| matches!(b, b'G' | b'C'))
+            .count()
+    }
+
+    /// Length of the longest single-base run in `seq` (e.g. 5 for AAAAA).
+    pub fn max_homopolymer_run(seq: &[u8]) -> usize {
+        seq.chunk_by(|a, b| a == b).map(<[u8]>::len).max().unwrap_or(0)
     }
 
     #[cfg(test)]
@@ -253,6 +1341,463 @@ pub mod thal {
             assert!(result2.temp < result1.temp);
             assert!(result2.dg > result1.dg);
         }
+
+        #[test]
+        fn test_is_self_complementary() {
+            assert_eq!(is_self_complementary(b"ATCGAT"), Some(true));
+            assert_eq!(is_self_complementary(b"GAATTC"), Some(true));
+            assert_eq!(is_self_complementary(b"ATCGATCG"), Some(false));
+            assert_eq!(is_self_complementary(b"ATNGAT"), None);
+            assert_eq!(is_self_complementary(b"ATCGAN"), None);
+            assert_eq!(complement(b'N'), None);
+        }
+
+        #[test]
+        fn test_reverse_complement_keeps_masking() {
+            // EcoRI site soft-masked inside an unmasked palindrome
+            let fwd = b"ACgaattcGT";
+            let rev = reverse_complement(fwd);
+            assert_eq!(rev, b"ACgaattcGT");
+            let masked = |s: &[u8]| s.iter().map(u8::is_ascii_lowercase).collect::<Vec<_>>();
+            assert_eq!(masked(fwd), masked(&rev));
+
+            assert_eq!(reverse_complement(b"aCgRn"), b"nYcGt");
+        }
+
+        #[test]
+        fn test_canonical_kmer_is_strand_independent() {
+            assert_eq!(canonical_kmer(b"TTGCA"), b"TGCAA");
+            assert_eq!(canonical_kmer(b"TGCAA"), b"TGCAA");
+            assert_eq!(canonical_kmer(b"GAATTC"), b"GAATTC");
+        }
+
+        #[test]
+        fn test_mfe_fold() {
+            let one_molar = ThalArgs { mv: 1000.0, ..create_default_args() };
+            let (dg, structure) = mfe_fold(b"GGGGAAAACCCC", &one_molar);
+            assert_eq!(structure, "((((....))))");
+            // Three GG/CC stacks closing a 4-base loop
+            let (h, s) = nn_params(b'G', b'G').unwrap();
+            let t = one_molar.temp.as_kelvin();
+            let expected = 3.0 * (h * CAL_PER_KCAL - t * s) + 3.5 * CAL_PER_KCAL;
+            assert!((dg - expected).abs() < 1e-6, "{} vs {}", dg, expected);
+
+            // Two hairpins side by side form a multiloop-free exterior fold
+            let (dg, structure) = mfe_fold(b"GGGGAAAACCCCAGGGGAAAACCCC", &one_molar);
+            assert_eq!(structure, "((((....)))).((((....))))");
+            assert!(dg < 2.0 * expected + 1e-6);
+
+            // Whatever folds, brackets nest and close Watson-Crick pairs
+            for seq in [&b"GCGCTTAGCGCAAGCGCTTAGCGCAAGCGCTTTTGCGCAAAA"[..], b"CCGGCCGGTTTTCCGGCCGG", b"ACGTTGCAACGTAGCT"] {
+                let (dg, structure) = mfe_fold(seq, &one_molar);
+                assert!(dg <= 0.0);
+                let mut open = Vec::new();
+                for (i, c) in structure.bytes().enumerate() {
+                    match c {
+                        b'(' => open.push(i),
+                        b')' => assert_eq!(complement(seq[open.pop().unwrap()]), Some(seq[i])),
+                        _ => {}
+                    }
+                }
+                assert!(open.is_empty(), "{}", structure);
+            }
+            assert_eq!(mfe_fold(b"AAAAAAAAAAAA", &one_molar), (0.0, "............".to_string()));
+            assert_eq!(mfe_fold(b"", &one_molar), (0.0, String::new()));
+            assert!(mfe_fold(&[b'A'; MFE_MAX_LEN + 1], &one_molar).0.is_nan());
+        }
+
+        #[test]
+        fn test_stack_histogram() {
+            let count = |seq: &[u8], stack: &[u8; 2]| {
+                stack_histogram(seq)[NN_STACKS.iter().position(|s| s == stack).unwrap()]
+            };
+            assert_eq!(count(b"AAttT", b"AA"), 3);
+            assert_eq!(count(b"AAttT", b"AT"), 1);
+            assert_eq!(count(b"GCGC", b"GC"), 2);
+            assert_eq!(count(b"GCGC", b"CG"), 1);
+            assert_eq!(stack_histogram(b"ACNGT").iter().sum::<usize>(), 2);
+            // Every step of an ACGT sequence lands in exactly one stack
+            let seq = b"GTCAGCTAGGCATCGAATCG";
+            assert_eq!(stack_histogram(seq).iter().sum::<usize>(), seq.len() - 1);
+        }
+
+        #[test]
+        fn test_stack_sums() {
+            // Lengths around the four-step lane groups of the simd path
+            let genome = b"GTCAGCTAGGCATCGAATCGTTGCCGATAGCTCAGGACTA";
+            for len in 0..genome.len() {
+                let seq = &genome[..len];
+                let (dh, ds) = stack_sums(seq).unwrap();
+                let steps: Vec<_> = seq.windows(2).map(|p| nn_params(p[0], p[1]).unwrap()).collect();
+                assert!((dh - steps.iter().map(|s| s.0).sum::<f64>()).abs() < 1e-9, "len {}", len);
+                assert!((ds - steps.iter().map(|s| s.1).sum::<f64>()).abs() < 1e-9, "len {}", len);
+            }
+            assert_eq!(stack_sums(b"CG"), Some((-10.6, -27.2)));
+            assert_eq!(stack_sums(b"ACGTACGTNA"), None);
+            assert_eq!(stack_sums(b"acgt"), None);
+        }
+
+        #[test]
+        fn test_match_bars() {
+            assert_eq!(match_bars(b"ACGTACGT", b"ACGAAcGN"), "||| ||| ");
+            assert_eq!(match_bars(b"NAC", b"NAG"), " | ");
+        }
+
+        #[test]
+        fn test_ligation_junction_tm() {
+            let args = create_default_args();
+            let (left, right) = (&b"GTCAGCTAGG"[..], &b"CATCGAATCG"[..]);
+            let tm = ligation_junction_tm(left, right, &args);
+            // primer3 oligotm (santalucia) for the joined 20-mer at 50 mM
+            // Na+, no Mg2+ and 50 nM oligo
+            assert!((tm - 52.96).abs() < 0.01, "{}", tm);
+            assert!(tm > calculate_thermo(left, &args).temp);
+            assert!(tm > calculate_thermo(right, &args).temp);
+            assert!(ligation_junction_tm(b"", right, &args).is_nan());
+            assert!(ligation_junction_tm(left, b"CANNG", &args).is_nan());
+        }
+
+        #[test]
+        fn test_tm_from_enthalpy_entropy() {
+            let seq = b"GTCAGCTAGGCATCGAATCG";
+            // At 1 M Na+ the NN result's ΔS carries no salt term
+            let one_molar = ThalArgs { mv: 1000.0, dv: 0.0, dntp: 0.0, ..create_default_args() };
+            let nn = calculate_thermo(seq, &one_molar);
+            let tm = tm_from_enthalpy_entropy(nn.dh / CAL_PER_KCAL, nn.ds, seq.len(), &one_molar);
+            assert!((tm - nn.temp).abs() < 1e-9);
+            let salty = calculate_thermo(seq, &create_default_args()).temp;
+            let tm = tm_from_enthalpy_entropy(nn.dh / CAL_PER_KCAL, nn.ds, seq.len(), &create_default_args());
+            assert!((tm - salty).abs() < 1e-9);
+        }
+
+        #[test]
+        fn test_match_score() {
+            let primer = b"ATCGATCGATCGATCGATCG";
+            assert_eq!(match_score(primer, &reverse_complement(primer)), 20);
+            assert_eq!(match_score(primer, primer), 0);
+            assert_eq!(match_score(b"acgt", b"ACGN"), 3);
+            // b's 3' end sits opposite a's 5' end
+            assert_eq!(match_score(b"AAAA", b"GGTT"), 2);
+        }
+
+        #[test]
+        fn test_calculate_thermo_santalucia_example() {
+            // SantaLucia 1998 worked example, 1 M Na+: ΔG37 = -5.35 kcal/mol
+            let mut args = create_default_args();
+            args.mv = 1000.0;
+            let result = calculate_thermo(b"CGTTGA", &args);
+            assert_eq!(result.msg, "");
+            assert!((result.dg / 1000.0 - -5.35).abs() < 0.1, "dG {}", result.dg);
+
+            assert!(!calculate_thermo(b"ACNGT", &args).msg.is_empty());
+            assert_eq!(validate_bases(b"AC-GT"), Err(ThalError::GapInSequence { position: 2 }));
+            assert_eq!(calculate_thermo(b"AC.GT", &args).msg, "gap at position 2 (use score_alignment for aligned input)");
+        }
+
+        #[test]
+        fn test_degenerate_windows_are_rejected() {
+            let args = create_default_args();
+            for seq in [&b""[..], b"A"] {
+                let result = calculate_thermo(seq, &args);
+                assert!(!result.msg.is_empty());
+                assert_eq!(result.temp, THAL_ERROR_SCORE);
+                assert_eq!(result.dg, 0.0);
+            }
+            assert!(calculate_thermo(b"AC", &args).msg.is_empty());
+        }
+
+        #[test]
+        fn test_calculate_thermo_symmetry_correction() {
+            let mut args = create_default_args();
+            args.mv = 1000.0;
+            // GAATTC: G/C ends (-2.8 x2) + stacks (-109.2) + symmetry (-1.4)
+            let result = calculate_thermo(b"GAATTC", &args);
+            assert!((result.ds - -116.2).abs() < 1e-9, "dS {}", result.ds);
+            // Non-palindromic GAATTG has no symmetry term
+            let hetero = calculate_thermo(b"GAATTG", &args);
+            assert!((hetero.ds - (-2.8 - 22.2 - 22.2 - 20.4 - 22.2 - 22.7 - 2.8)).abs() < 1e-9);
+
+            // primer3 oligotm (santalucia) for the EcoRI palindrome at 50 mM
+            // Na+, 1.5 mM Mg2+, 0.6 mM dNTP and 50 nM oligo (C_T, not C_T/4)
+            let args = ThalArgs { dv: 1.5, dntp: 0.6, ..create_default_args() };
+            let result = calculate_thermo(b"GAATTCGAATTC", &args);
+            assert!((result.dh - -89200.0).abs() < 1e-6, "dH {}", result.dh);
+            assert!((result.ds - -259.92).abs() < 0.01, "dS {}", result.ds);
+            assert!((result.dg - -8585.12).abs() < 0.01, "dG {}", result.dg);
+            assert!((result.temp - 30.94).abs() < 0.01, "Tm {}", result.temp);
+        }
+
+        #[test]
+        fn test_symmetry_correction_off_scores_heterodimer() {
+            let args = create_default_args();
+            let naive = ThalArgs { symmetry_correction: false, ..create_default_args() };
+            let palindrome = b"GAATTCGAATTC";
+            let (corrected, forced) = (calculate_thermo(palindrome, &args), calculate_thermo(palindrome, &naive));
+            assert!((corrected.ds - forced.ds - -1.4).abs() < 1e-9);
+            assert!(forced.temp < corrected.temp);
+            let seq = b"ATCGATCGATCG";
+            assert!(calculate_thermo(seq, &args).approx_eq(&calculate_thermo(seq, &naive), 0.0));
+        }
+
+        #[test]
+        fn test_score_alignment() {
+            let args = create_default_args();
+            let top = b"ATGCGATCGATCGTTA";
+            let perfect_bottom: Vec<u8> = top.iter().map(|&b| complement(b).unwrap()).collect();
+            let perfect = score_alignment(top, &perfect_bottom, &args);
+            let direct = calculate_thermo(top, &args);
+            assert_eq!(perfect.msg, "");
+            assert!((perfect.dg - direct.dg).abs() < 1e-6);
+            assert_eq!(perfect.align_end_1, 16);
+
+            let mismatch = score_alignment(top, b"TACGCTAGGTAGCAAT", &args);
+            let bulge = score_alignment(b"ATGCGATCGATCGTTA", b"TACGCTA-CTAGCAAT", &args);
+            let internal = score_alignment(b"ATGCGAT--ATCGTTA", b"TACGCTAAATAGCAAT", &args);
+            for worse in [&mismatch, &bulge, &internal] {
+                assert_eq!(worse.msg, "");
+                assert!(worse.dg > perfect.dg);
+            }
+            assert_eq!(bulge.align_end_2, 15);
+
+            assert!(!score_alignment(b"ACGT", b"TGC", &args).msg.is_empty());
+        }
+
+        #[test]
+        fn test_three_prime_stability() {
+            let args = create_default_args();
+            let gc_end = three_prime_stability(b"ATATATATATGCGCG", 5, &args);
+            let at_end = three_prime_stability(b"GCGCGCGCGCATATA", 5, &args);
+            assert!(gc_end < at_end);
+            assert_eq!(gc_end, calculate_thermo(b"GCGCG", &args).dg);
+        }
+
+        #[test]
+        fn test_half_stability() {
+            let args = create_default_args();
+            let (five, three) = half_stability(b"ATATATATATGCGCGCGC", &args);
+            assert!(three < five);
+            assert_eq!(five, calculate_thermo(b"ATATATATA", &args).dg);
+            assert_eq!(three, calculate_thermo(b"TGCGCGCGC", &args).dg);
+            assert_eq!(half_stability(b"ACG", &args).0, 0.0);
+        }
+
+        #[test]
+        fn test_tm_is_reliable() {
+            let args = create_default_args();
+            assert!(tm_is_reliable(b"ATCGATCGATCGATCGATCG", &args));
+            assert_eq!(tm_warnings(b"ACGT", &args), vec!["length outside 8-60 nt"]);
+            assert!(!tm_is_reliable(b"GGGGCCCCGGGGCCCCGGGG", &args));
+
+            let mut low_salt = args.clone();
+            low_salt.mv = 10.0;
+            assert!(!tm_is_reliable(b"ATCGATCGATCGATCGATCG", &low_salt));
+        }
+
+        #[test]
+        fn test_wallace_tm() {
+            assert_eq!(wallace_tm(b"ATGC"), 12.0);
+            assert_eq!(wallace_tm(b"ATCGATCGATCGATCGATCG"), 60.0);
+        }
+
+        #[test]
+        fn test_gc_clamp_count() {
+            assert_eq!(gc_clamp_count(b"GGGGGATATA", 5), 0);
+            assert_eq!(gc_clamp_count(b"AAAAATTAGC", 5), 2);
+            assert_eq!(gc_clamp_count(b"GC", 5), 2);
+        }
+
+        #[test]
+        fn test_ambiguous_fraction() {
+            assert_eq!(ambiguous_fraction(b"ACGTacgt"), 0.0);
+            assert_eq!(ambiguous_fraction(b"NNNN"), 1.0);
+            assert_eq!(ambiguous_fraction(b"ACnN"), 0.5);
+        }
+
+        #[test]
+        fn test_max_homopolymer_run() {
+            assert_eq!(max_homopolymer_run(b""), 0);
+            assert_eq!(max_homopolymer_run(b"ACGT"), 1);
+            assert_eq!(max_homopolymer_run(b"ACAAAAAGTTT"), 5);
+        }
+
+        #[test]
+        fn test_delta_g_effective() {
+            let args = create_default_args();
+            let seq = b"ATCGATCGATCGATCGATCG";
+            let tm = calculate_thermo(seq, &args).temp;
+            assert!(delta_g_effective(seq, &args, Temperature::celsius(tm)).abs() < 1e-6);
+            assert!(delta_g_effective(seq, &args, Temperature::celsius(tm - 10.0)) < 0.0);
+            // working concentration is far below 1 M, so binding is weaker
+            let standard = calculate_thermo(seq, &args).dg;
+            assert!(delta_g_effective(seq, &args, Temperature::celsius(37.0)) > standard);
+            assert!(delta_g_effective(b"A", &args, Temperature::celsius(37.0)).is_nan());
+        }
+
+        #[test]
+        fn test_iupac_bases() {
+            assert_eq!(iupac_bases(b'R'), Some(&b"AG"[..]));
+            assert_eq!(iupac_bases(b'N').unwrap().len(), 4);
+            assert_eq!(iupac_bases(b'-'), None);
+        }
+
+        #[test]
+        fn test_stable_kmers() {
+            let args = create_default_args();
+            assert_eq!(stable_kmers(3, &args, f64::INFINITY).len(), 64);
+            let stable = stable_kmers(4, &args, -2000.0);
+            assert!(!stable.is_empty() && stable.len() < 256);
+            assert!(stable.iter().all(|kmer| calculate_thermo(kmer, &args).dg <= -2000.0));
+            assert!(stable.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(stable_kmers_iter(12, &args, -2000.0).next().unwrap().len(), 12);
+            assert!(stable_kmers(1, &args, 0.0).is_empty());
+        }
+
+        #[test]
+        fn test_calculate_thermo_against() {
+            let args = create_default_args();
+            let primer = b"GTCAGCTAGGCATCGAATCG";
+            let perfect = calculate_thermo_against(primer, primer, &args);
+            assert!(perfect.approx_eq(&calculate_thermo(primer, &args), 1e-6));
+            let mismatched = calculate_thermo_against(primer, b"GTCAGCTAGGTATCGAATCG", &args);
+            assert!(mismatched.temp < perfect.temp);
+            let masked = calculate_thermo_against(primer, b"GTCAGCTAGGNATCGAATCG", &args);
+            assert!(masked.approx_eq(&mismatched, 1e-6));
+        }
+
+        #[test]
+        fn test_delta_g_per_bp() {
+            assert_eq!(delta_g_per_bp(-19.0, 20), -1.0);
+            assert!(delta_g_per_bp(-1.0, 1).is_nan());
+        }
+
+        #[test]
+        fn test_delta_tm() {
+            let low = create_default_args();
+            let high = ThalArgs { mv: 500.0, ..create_default_args() };
+            let seq = b"GTCAGCTAGGCATCGAATCG";
+            let shift = delta_tm(seq, &low, &high);
+            assert!(shift > 0.0);
+            assert!((shift + delta_tm(seq, &high, &low)).abs() < 1e-9);
+            assert_eq!(delta_tm(seq, &low, &low), 0.0);
+            assert!(delta_tm(b"A", &low, &high).is_nan());
+        }
+
+        #[test]
+        fn test_salt_methods() {
+            let seq = b"GTCAGCTAGGCATCGAATCG";
+            let with = |method, mv| {
+                let args = ThalArgs { mv, dv: 0.0, salt_method: method, ..create_default_args() };
+                (calculate_thermo(seq, &args), args)
+            };
+            // At 1 M Na+ every correction vanishes
+            let (one_molar, _) = with(SaltMethod::Santalucia, 1000.0);
+            for method in [SaltMethod::Schildkraut, SaltMethod::Owczarzy] {
+                assert!(with(method, 1000.0).0.approx_eq(&one_molar, 1e-6));
+            }
+
+            let ln_na = (0.05f64).ln();
+            let (schildkraut, _) = with(SaltMethod::Schildkraut, 50.0);
+            assert!((schildkraut.temp - (one_molar.temp + 16.6 * 0.05f64.log10())).abs() < 1e-9);
+            let (owczarzy, args) = with(SaltMethod::Owczarzy, 50.0);
+            let inv = 1.0 / (one_molar.temp + ABSOLUTE_ZERO)
+                + (4.29 * gc_fraction(seq) - 3.95) * 1e-5 * ln_na
+                + 9.40e-6 * ln_na * ln_na;
+            assert!((owczarzy.temp - (1.0 / inv - ABSOLUTE_ZERO)).abs() < 1e-9);
+            // ΔS is reported so that it reproduces the corrected Tm
+            let c_term = R_CAL * concentration_term(false, &args);
+            assert!((owczarzy.dh / (owczarzy.ds + c_term) - ABSOLUTE_ZERO - owczarzy.temp).abs() < 1e-9);
+            assert!(with(SaltMethod::Santalucia, 50.0).0.temp < one_molar.temp);
+
+            // primer3 oligotm (tm_method santalucia) at 50 mM Na+, 1.5 mM
+            // Mg2+, 0.6 mM dNTP and 50 nM oligo, per salt_corrections method
+            for (method, expected) in [
+                (SaltMethod::Santalucia, 58.58),
+                (SaltMethod::Schildkraut, 54.49),
+                (SaltMethod::Owczarzy, 60.76),
+            ] {
+                let args = ThalArgs { dv: 1.5, dntp: 0.6, salt_method: method, ..create_default_args() };
+                let tm = calculate_thermo(seq, &args).temp;
+                assert!((tm - expected).abs() < 0.01, "{:?}: {}", method, tm);
+            }
+        }
+
+        #[test]
+        fn test_dg_bounds_contain_every_kmer() {
+            let args = ThalArgs { dv: 1.5, dntp: 0.6, ..create_default_args() };
+            let k = 6;
+            let bounds = DgBounds::new(k, &args);
+            let threshold = -7000.0;
+            let mut pruned = 0;
+            for index in 0..1u32 << (2 * k) {
+                let kmer: Vec<u8> = (0..k).map(|i| b"ACGT"[((index >> (2 * (k - 1 - i))) & 3) as usize]).collect();
+                let gc = kmer.iter().filter(|&&b| matches!(b, b'G' | b'C')).count();
+                let dg = calculate_thermo(&kmer, &args).dg;
+                assert!(bounds.lower(gc) <= dg + 1e-6 && dg <= bounds.upper(gc) + 1e-6, "{:?}", kmer);
+                if !bounds.can_pass(gc, threshold) {
+                    assert!(dg > threshold);
+                    pruned += 1;
+                }
+            }
+            // AT-rich 6-mers cannot reach -7 kcal/mol
+            assert!(pruned > 0);
+            let other = ThalArgs { salt_method: SaltMethod::Owczarzy, ..args };
+            assert!(DgBounds::new(k, &other).can_pass(0, f64::NEG_INFINITY));
+        }
+
+        #[test]
+        fn test_normalize_sequence() {
+            assert_eq!(normalize_sequence(" acgT\nGG ", false), Ok(b"ACGTGG".to_vec()));
+            assert_eq!(normalize_sequence("AC-G.T", true), Ok(b"ACGT".to_vec()));
+            assert_eq!(normalize_sequence("AC-GT", false), Err(ThalError::GapInSequence { position: 2 }));
+            assert_eq!(normalize_sequence("ACNT", true), Err(ThalError::InvalidBase { base: 'N', position: 2 }));
+            assert_eq!(normalize_sequence(" -- ", true), Err(ThalError::EmptySequence));
+        }
+
+        #[test]
+        fn test_heterodimer_validates_both_strands() {
+            let args = create_default_args();
+            assert_eq!(heterodimer(b"", b"ACGT", &args).unwrap_err(), ThalError::EmptySequence);
+            assert_eq!(heterodimer(b"ACGT", b"ACNT", &args).unwrap_err(), ThalError::InvalidBase { base: 'N', position: 2 });
+        }
+
+        #[test]
+        fn test_tm_asymmetric() {
+            let args = create_default_args();
+            let seq = b"GTCAGCTAGGCATCGAATCG";
+            let tm = calculate_thermo(seq, &args).temp;
+            assert!((tm_asymmetric(seq, &args, args.dna_conc, 1.0) - tm).abs() < 1e-9);
+            // Excess of one strand raises the Tm; the ratio is symmetric
+            let excess = tm_asymmetric(seq, &args, args.dna_conc, 10.0);
+            assert!(excess > tm);
+            assert!((tm_asymmetric(seq, &args, args.dna_conc, 0.1) - excess).abs() < 1e-9);
+            assert!(tm_asymmetric(seq, &args, args.dna_conc, 0.0).is_nan());
+        }
+
+        #[test]
+        fn test_salt_correction_ds() {
+            assert_eq!(salt_correction_ds(20, 1000.0, 0.0, 0.0), 0.0);
+            // 50 mM Na+: 0.368 * 19 * ln(0.05)
+            assert!((salt_correction_ds(20, 50.0, 0.0, 0.0) - -20.946).abs() < 1e-3);
+            // dNTP chelates all Mg2+, leaving the Na+ term
+            assert_eq!(salt_correction_ds(20, 50.0, 0.5, 0.6), salt_correction_ds(20, 50.0, 0.0, 0.0));
+            let args = create_default_args();
+            let one_molar = ThalArgs { mv: 1000.0, ..create_default_args() };
+            let seq = b"GTCAGCTAGGCATCGAATCG";
+            let shift = calculate_thermo(seq, &args).ds - calculate_thermo(seq, &one_molar).ds;
+            assert!((shift - salt_correction_ds(seq.len(), args.mv, args.dv, args.dntp)).abs() < 1e-9);
+        }
+
+        #[test]
+        fn test_approx_eq() {
+            let args = create_default_args();
+            let a = calculate_thermo(b"ATCGATCGATCGATCGATCG", &args);
+            let mut b = a.clone();
+            b.dg += 0.5;
+            assert!(a.approx_eq(&b, 1.0));
+            assert!(!a.approx_eq(&b, 0.1));
+            let err = calculate_thermo(b"A", &args);
+            assert!(err.approx_eq(&err.clone(), 0.0));
+        }
     }
 }
 
@@ -0,0 +1,152 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Half-open `[start, end)` intervals per seqid, as read from a BED file.
+/// Intervals for each seqid are kept sorted with overlaps merged so a base
+/// is never scanned twice.
+#[derive(Debug, Default, Clone)]
+pub struct Regions {
+    by_seq: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl Regions {
+    pub fn from_bed(path: &str) -> io::Result<Self> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse BED3+ lines; `track`/`browser`/`#` lines and blanks are ignored.
+    pub fn parse<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut by_seq: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        for (lineno, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim_end();
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+            let bad = |what: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("BED line {}: {}", lineno + 1, what),
+                )
+            };
+            let mut fields = line.split('\t');
+            let seq_id = fields.next().ok_or_else(|| bad("missing chrom"))?;
+            let start: usize = fields
+                .next()
+                .and_then(|f| f.trim().parse().ok())
+                .ok_or_else(|| bad("invalid chromStart"))?;
+            let end: usize = fields
+                .next()
+                .and_then(|f| f.trim().parse().ok())
+                .ok_or_else(|| bad("invalid chromEnd"))?;
+            if end < start {
+                return Err(bad("chromEnd before chromStart"));
+            }
+            by_seq.entry(seq_id.to_string()).or_default().push((start, end));
+        }
+
+        for intervals in by_seq.values_mut() {
+            intervals.sort_unstable();
+            let mut merged: Vec<(usize, usize)> = Vec::with_capacity(intervals.len());
+            for &(s, e) in intervals.iter() {
+                match merged.last_mut() {
+                    Some(last) if s <= last.1 => last.1 = last.1.max(e),
+                    _ => merged.push((s, e)),
+                }
+            }
+            *intervals = merged;
+        }
+        Ok(Regions { by_seq })
+    }
+
+    /// Intervals for `seq_id`, clamped to `seq_len`. Empty if the record
+    /// has no regions.
+    pub fn for_record(&self, seq_id: &str, seq_len: usize) -> Vec<(usize, usize)> {
+        self.by_seq
+            .get(seq_id)
+            .map(|v| {
+                v.iter()
+                    .map(|&(s, e)| (s.min(seq_len), e.min(seq_len)))
+                    .filter(|&(s, e)| s < e)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Which records to scan, by id. A listed id matches a record's full
+/// header line or its first word, so accessions work on described records.
+#[derive(Debug, Default, Clone)]
+pub struct SeqFilter {
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+}
+
+impl SeqFilter {
+    /// From --include-seqs / --exclude-seqs values; see `parse_ids`.
+    pub fn new(include: Option<&str>, exclude: Option<&str>) -> io::Result<Self> {
+        Ok(SeqFilter {
+            include: include.map(Self::parse_ids).transpose()?,
+            exclude: exclude.map(Self::parse_ids).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// Ids from `arg`: a file of one id per line (blank and `#` lines
+    /// skipped) if it names one, else a comma-separated list.
+    pub fn parse_ids(arg: &str) -> io::Result<HashSet<String>> {
+        let ids: HashSet<String> = if Path::new(arg).is_file() {
+            fs::read_to_string(arg)?
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(str::to_string)
+                .collect()
+        } else {
+            arg.split(',').map(str::trim).filter(|id| !id.is_empty()).map(str::to_string).collect()
+        };
+        Ok(ids)
+    }
+
+    pub fn keeps(&self, seq_id: &str) -> bool {
+        let first_word = seq_id.split_whitespace().next().unwrap_or("");
+        let listed = |ids: &HashSet<String>| ids.contains(seq_id) || ids.contains(first_word);
+        self.include.as_ref().is_none_or(listed) && !listed(&self.exclude)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_merges_and_clamps() {
+        let bed = "track name=exons\n# comment\nchr1\t100\t200\tx\nchr1\t150\t250\nchr1\t10\t20\nchr2\t5\t50\n";
+        let regions = Regions::parse(bed.as_bytes()).unwrap();
+        assert_eq!(regions.for_record("chr1", 1000), vec![(10, 20), (100, 250)]);
+        assert_eq!(regions.for_record("chr2", 30), vec![(5, 30)]);
+        assert!(regions.for_record("chr3", 1000).is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_lines() {
+        assert!(Regions::parse("chr1\tabc\t10\n".as_bytes()).is_err());
+        assert!(Regions::parse("chr1\t20\t10\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_seq_filter_matches_full_id_or_first_word() {
+        let filter = SeqFilter::new(Some("chr1, chr2 desc,"), Some("chr2 desc")).unwrap();
+        assert!(filter.keeps("chr1"));
+        assert!(filter.keeps("chr1 Homo sapiens chromosome 1"));
+        assert!(!filter.keeps("chr2 desc"));
+        assert!(!filter.keeps("chr3"));
+        let exclude_only = SeqFilter::new(None, Some("chrM")).unwrap();
+        assert!(exclude_only.keeps("chr3") && !exclude_only.keeps("chrM"));
+    }
+}
@@ -0,0 +1,1129 @@
+use crate::thal::{self, ThalArgs, ThalMode};
+use aho_corasick::AhoCorasick;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Exact-match seed length used to find candidate windows.
+pub const SEED_LEN: usize = 7;
+/// Default bases per parallel work unit.
+pub const CHUNK_SIZE: usize = 1_000_000;
+/// Default bases shared by adjacent chunks so windows spanning a boundary
+/// are seen; must be at least the longest motif minus one.
+pub const CHUNK_OVERLAP: usize = 100;
+
+/// How windows overlapping ambiguous genome bases are scored.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, clap::ValueEnum)]
+pub enum GenomeAmbiguity {
+    /// Skip windows over --max-ambiguous; score the rest unchanged
+    #[default]
+    Skip,
+    /// Resolve each IUPAC code to the primer's base where the code allows it
+    ResolveBest,
+    /// Score each IUPAC code as a mismatch against the primer
+    TreatAsMismatch,
+}
+
+/// Primers prepared for scanning: each contributes its forward sequence and
+/// reverse complement as consecutive motifs (so `Strand::of_motif` gives the
+/// strand), with the seed automaton built over all of them.
+pub struct Patterns {
+    motifs: Vec<Vec<u8>>,
+    /// Scoring conditions per motif, shared by both strands of a primer.
+    args: Vec<ThalArgs>,
+    /// G/C-count ΔG bounds per motif, for pruning `ScoreModel::WindowDuplex`
+    /// windows.
+    bounds: Vec<thal::DgBounds>,
+    seed_to_motifs: Vec<Vec<(usize, usize)>>,
+    ac: AhoCorasick,
+}
+
+impl Patterns {
+    /// Build from uppercased primer sequences and the conditions each is
+    /// scored under.
+    pub fn new<I: IntoIterator<Item = (Vec<u8>, ThalArgs)>>(primers: I) -> Self {
+        let mut motifs = Vec::new();
+        let mut args = Vec::new();
+        let mut bounds = Vec::new();
+        // Mapping from unique seed to list of (motif_idx, offset)
+        let mut seed_map: HashMap<Vec<u8>, Vec<(usize, usize)>> = HashMap::new();
+        for (seq, primer_args) in primers {
+            let rc = thal::reverse_complement(&seq);
+            let primer_bounds = thal::DgBounds::new(seq.len(), &primer_args);
+            for s in [seq, rc] {
+                let motif_idx = motifs.len();
+                if s.len() >= SEED_LEN {
+                    for offset in 0..=(s.len() - SEED_LEN) {
+                        let seed = s[offset..offset + SEED_LEN].to_vec();
+                        seed_map.entry(seed).or_default().push((motif_idx, offset));
+                    }
+                }
+                motifs.push(s);
+                args.push(primer_args.clone());
+                bounds.push(primer_bounds.clone());
+            }
+        }
+
+        let (unique_seeds, seed_to_motifs): (Vec<_>, Vec<_>) = seed_map.into_iter().unzip();
+        // Match seeds case-insensitively so callers may pass soft-masked
+        // sequence as-is; only windows that get scored are uppercased.
+        let ac = AhoCorasick::builder()
+            .ascii_case_insensitive(true)
+            .build(&unique_seeds)
+            .expect("seed automaton");
+        Patterns { motifs, args, bounds, seed_to_motifs, ac }
+    }
+
+    pub fn motif(&self, motif_idx: usize) -> &[u8] {
+        &self.motifs[motif_idx]
+    }
+
+    /// Number of motifs (twice the number of primers).
+    pub fn len(&self) -> usize {
+        self.motifs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.motifs.is_empty()
+    }
+
+    pub fn max_motif_len(&self) -> usize {
+        self.motifs.iter().map(Vec::len).max().unwrap_or(0)
+    }
+}
+
+/// How each candidate window is scored against its motif.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum ScoreModel {
+    /// primer3 thal alignment of the primer to the window (`thal::thal`).
+    #[default]
+    Thal,
+    /// Native NN model of the window paired with its exact complement
+    /// (`thal::calculate_thermo`), whatever the primer's mismatches.
+    WindowDuplex,
+    /// Native NN model of the primer aligned base for base to the window
+    /// (`thal::calculate_thermo_against`).
+    PrimerOnWindow,
+}
+
+/// Window filters and extras applied while scanning.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Max ΔG (kcal/mol) for a window to be reported.
+    pub threshold: f64,
+    /// Min ΔG (kcal/mol); windows more stable than this are dropped.
+    pub min_dg: f64,
+    /// Skip windows whose fraction of non-ACGT bases exceeds this.
+    pub max_ambiguous: f64,
+    pub genome_ambiguity: GenomeAmbiguity,
+    pub model: ScoreModel,
+    /// Require a G/C among the N window bases paired with the primer's 3'
+    /// end (`clamp_gc_count`).
+    pub gc_clamp: Option<usize>,
+    /// Also compute the native NN Tm of the aligned primer (`Hit::nn_tm`).
+    pub nn_tm: bool,
+    /// Also record the window's GC fraction (`Hit::gc`).
+    pub gc: bool,
+    /// Also compute the native NN ΔG of the window's own perfect duplex and
+    /// of the primer aligned to it (`Hit::window_dg`, `Hit::primer_dg`).
+    pub compare_dg: bool,
+    /// Score each window against both motifs of its primer and keep the
+    /// more stable, with the other's ΔG in `Hit::opposite_dg`.
+    pub best_strand: bool,
+    /// Stop scanning once the funnel has counted this many passing windows.
+    pub stop_after: Option<usize>,
+    /// Stop scanning once this flag is set, e.g. by `exit::handle_interrupt`.
+    pub cancel: Option<&'static AtomicBool>,
+    /// Only count seed matches (`Funnel::seed_counts`), extending none.
+    pub seeds_only: bool,
+    /// Bases per parallel work unit. Chunks start every
+    /// `chunk_size - chunk_overlap` bases (at least 1).
+    pub chunk_size: usize,
+    /// Bases shared by adjacent chunks. Raised at scan time to the longest
+    /// motif minus one, so no window is missed whatever the motif length.
+    pub chunk_overlap: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            threshold: -10.0,
+            min_dg: f64::NEG_INFINITY,
+            max_ambiguous: 0.0,
+            genome_ambiguity: GenomeAmbiguity::Skip,
+            model: ScoreModel::Thal,
+            gc_clamp: None,
+            nn_tm: false,
+            gc: false,
+            compare_dg: false,
+            best_strand: false,
+            stop_after: None,
+            cancel: None,
+            seeds_only: false,
+            chunk_size: CHUNK_SIZE,
+            chunk_overlap: CHUNK_OVERLAP,
+        }
+    }
+}
+
+/// Which strand of the record a primer binds.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Strand {
+    /// The primer reads as given.
+    Forward,
+    /// The primer's reverse complement reads on the record.
+    Reverse,
+}
+
+impl Strand {
+    /// Strand of a `Patterns` motif: motifs come in [forward, reverse
+    /// complement] pairs, so the index parity decides it.
+    pub fn of_motif(motif_idx: usize) -> Self {
+        if motif_idx % 2 == 0 { Strand::Forward } else { Strand::Reverse }
+    }
+}
+
+/// `+`/`-`, or `FWD`/`REV` with the alternate flag (`{:#}`).
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match (self, f.alternate()) {
+            (Strand::Forward, false) => "+",
+            (Strand::Reverse, false) => "-",
+            (Strand::Forward, true) => "FWD",
+            (Strand::Reverse, true) => "REV",
+        })
+    }
+}
+
+impl Serialize for Strand {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts either `Display` form.
+impl<'de> Deserialize<'de> for Strand {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "+" | "FWD" => Ok(Strand::Forward),
+            "-" | "REV" => Ok(Strand::Reverse),
+            other => Err(serde::de::Error::custom(format!("invalid strand {:?}", other))),
+        }
+    }
+}
+
+/// A passing window, positioned on the forward strand of its record. This
+/// is the one record every output format renders: `Serialize` for
+/// structured writers, `Display` for the core tab-separated columns.
+/// Skipped fields deserialize as zero.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hit {
+    pub seq_id: String,
+    pub pos: usize,
+    pub strand: Strand,
+    /// kcal/mol
+    pub dg: f64,
+    /// °C
+    pub tm: f64,
+    /// kcal/mol
+    pub dh: f64,
+    /// cal/K/mol
+    pub ds: f64,
+    pub motif: String,
+    /// GC fraction of the matched window (`ScanOptions::gc`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gc: Option<f64>,
+    /// Native NN Tm of the primer aligned base for base to the window
+    /// (`ScanOptions::nn_tm`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nn_tm: Option<f64>,
+    /// Native NN ΔG (kcal/mol) of the window paired with its exact
+    /// complement: how stable the site is as a standalone duplex
+    /// (`ScanOptions::compare_dg`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_dg: Option<f64>,
+    /// Native NN ΔG (kcal/mol) of the primer aligned base for base to the
+    /// window, mismatches scored (`ScanOptions::compare_dg`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primer_dg: Option<f64>,
+    /// ΔG (kcal/mol) of the same primer on the other strand at this
+    /// position, when `merge_strands` folded that hit into this one or
+    /// `ScanOptions::best_strand` scored it on the same window.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opposite_dg: Option<f64>,
+    #[serde(skip)]
+    pub motif_idx: usize,
+    /// Index of the chunk (within its scanned interval) that produced the
+    /// hit, and the hit's offset from that chunk's start.
+    #[serde(skip)]
+    pub chunk: usize,
+    #[serde(skip)]
+    pub chunk_offset: usize,
+}
+
+impl Hit {
+    /// Names of the columns produced by `fields`.
+    pub const COLUMNS: [&'static str; 7] = ["seq_id", "pos", "dg", "tm", "dh", "ds", "motif"];
+
+    /// The core output columns, with `precision` decimals on the
+    /// thermodynamic values.
+    pub fn fields(&self, precision: usize) -> Vec<String> {
+        vec![
+            self.seq_id.clone(),
+            self.pos.to_string(),
+            format!("{:.*}", precision, self.dg),
+            format!("{:.*}", precision, self.tm),
+            format!("{:.*}", precision, self.dh),
+            format!("{:.*}", precision, self.ds),
+            self.motif.clone(),
+        ]
+    }
+}
+
+/// Tab-separated `Hit::COLUMNS`; the format precision (default 2) applies
+/// to the thermodynamic values, e.g. `format!("{:.4}", hit)`.
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.fields(f.precision().unwrap_or(2)).join("\t"))
+    }
+}
+
+/// Append `hit` to a MessagePack stream as a map keyed by field name, the
+/// compact binary counterpart of the text formats; `read_msgpack_hits`
+/// decodes the stream.
+pub fn write_msgpack_hit<W: Write>(writer: &mut W, hit: &Hit) -> io::Result<()> {
+    rmp_serde::encode::write_named(writer, hit).map_err(io::Error::other)
+}
+
+/// Decode a stream written by `write_msgpack_hit`, hit by hit, ending at a
+/// clean end of input.
+pub fn read_msgpack_hits<R: Read>(reader: R) -> impl Iterator<Item = io::Result<Hit>> {
+    let mut de = rmp_serde::Deserializer::new(io::BufReader::new(reader));
+    std::iter::from_fn(move || {
+        // Only input ending between hits is a clean end; a cut inside one is
+        // a decode error
+        match de.get_mut().fill_buf() {
+            Ok([]) => return None,
+            Ok(_) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Hit::deserialize(&mut de).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    })
+}
+
+/// What became of one (seed match, candidate motif) pair during extension.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SeedOutcome {
+    /// Scored and within the ΔG limits.
+    Passed,
+    /// Scored but outside the ΔG limits.
+    FailedDg,
+    /// Above the ΔG threshold whatever its stacks, by its G/C count alone
+    /// (`thal::DgBounds`), so never scored.
+    Pruned,
+    /// The full window would start before the chunk.
+    ClippedStart,
+    /// The full window would run past the chunk end.
+    ClippedEnd,
+    /// The window starts in the overlap, so the next chunk scores it.
+    OtherChunk,
+    /// Already evaluated from another seed of the same motif.
+    Duplicate,
+    /// Too many non-ACGT bases (`max_ambiguous`), or a base the native
+    /// `ScoreModel`s cannot score.
+    Ambiguous,
+    /// No G/C where the primer's 3' end binds (`gc_clamp`).
+    NoGcClamp,
+}
+
+impl fmt::Display for SeedOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            SeedOutcome::Passed => "passed",
+            SeedOutcome::FailedDg => "failed-dg",
+            SeedOutcome::Pruned => "pruned",
+            SeedOutcome::ClippedStart => "clipped-start",
+            SeedOutcome::ClippedEnd => "clipped-end",
+            SeedOutcome::OtherChunk => "other-chunk",
+            SeedOutcome::Duplicate => "duplicate",
+            SeedOutcome::Ambiguous => "ambiguous",
+            SeedOutcome::NoGcClamp => "no-gc-clamp",
+        })
+    }
+}
+
+/// One seed match tried against one motif, recorded when the funnel traces.
+#[derive(Debug, Clone)]
+pub struct SeedTrace {
+    pub seq_id: String,
+    /// Start of the matched seed on the record.
+    pub seed_pos: usize,
+    pub motif_idx: usize,
+    /// Offset of the seed within the motif.
+    pub offset: usize,
+    pub chunk: usize,
+    pub outcome: SeedOutcome,
+}
+
+/// Counts at each stage of the seed-and-extend funnel.
+#[derive(Default)]
+pub struct Funnel {
+    pub seed_hits: AtomicUsize,
+    pub full_length: AtomicUsize,
+    pub passing: AtomicUsize,
+    pub reported: AtomicUsize,
+    /// Per-seed extension outcomes, collected only when set (`with_trace`).
+    pub trace: Option<Mutex<Vec<SeedTrace>>>,
+    /// ΔG of every scored window, passing or not, collected only when set.
+    pub survey: Option<DgHistogram>,
+    /// Seed matches per motif, one for each offset the matched seed occurs
+    /// at in the motif, collected only when set.
+    pub seed_counts: Option<Vec<AtomicUsize>>,
+}
+
+/// Fixed-bin histogram of window ΔG (kcal/mol), filled concurrently by the
+/// scan to estimate genome-wide percentiles in constant memory. Values
+/// outside `[MIN, MAX)` count in the end bins.
+pub struct DgHistogram {
+    bins: Vec<AtomicUsize>,
+}
+
+impl DgHistogram {
+    pub const MIN: f64 = -80.0;
+    pub const MAX: f64 = 20.0;
+    pub const BIN_WIDTH: f64 = 0.05;
+
+    pub fn new() -> Self {
+        let n = ((Self::MAX - Self::MIN) / Self::BIN_WIDTH).round() as usize;
+        DgHistogram { bins: (0..n).map(|_| AtomicUsize::new(0)).collect() }
+    }
+
+    pub fn add(&self, dg: f64) {
+        if dg.is_nan() {
+            return;
+        }
+        let bin = ((dg - Self::MIN) / Self::BIN_WIDTH).floor().clamp(0.0, (self.bins.len() - 1) as f64);
+        Funnel::add(&self.bins[bin as usize], 1);
+    }
+
+    /// Number of values added.
+    pub fn count(&self) -> usize {
+        self.bins.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Upper edge of the bin holding the `q` quantile (0..=1): at least that
+    /// fraction of values are below it, to within one bin. None when empty.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let n = self.count();
+        if n == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * n as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+        for (i, bin) in self.bins.iter().enumerate() {
+            seen += bin.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Some(Self::MIN + (i + 1) as f64 * Self::BIN_WIDTH);
+            }
+        }
+        Some(Self::MAX)
+    }
+}
+
+impl Default for DgHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Funnel {
+    /// A funnel that also records every seed extension in `trace`.
+    pub fn with_trace() -> Self {
+        Funnel { trace: Some(Mutex::new(Vec::new())), ..Funnel::default() }
+    }
+
+    /// Take the recorded seed extensions, in no particular order.
+    pub fn take_trace(&self) -> Vec<SeedTrace> {
+        self.trace.as_ref().map_or_else(Vec::new, |trace| std::mem::take(&mut *trace.lock().unwrap()))
+    }
+
+    pub fn add(counter: &AtomicUsize, n: usize) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// One-line summary on stderr.
+    pub fn report(&self) {
+        eprintln!("seed hits: {}\tfull-length windows: {}\tpassing dG: {}\treported: {}",
+            self.seed_hits.load(Ordering::Relaxed),
+            self.full_length.load(Ordering::Relaxed),
+            self.passing.load(Ordering::Relaxed),
+            self.reported.load(Ordering::Relaxed));
+    }
+}
+
+/// Rewrite the IUPAC codes in an uppercased genome `window` against `motif`,
+/// the primer as it reads on this strand. `ResolveBest` picks the primer's
+/// base when the code includes it (else the code's first base);
+/// `TreatAsMismatch` picks the first of A/C/G differing from the primer.
+/// Bytes that are not IUPAC codes are left alone.
+pub fn resolve_ambiguity(window: &mut [u8], motif: &[u8], policy: GenomeAmbiguity) {
+    if policy == GenomeAmbiguity::Skip {
+        return;
+    }
+    for (w, &m) in window.iter_mut().zip(motif) {
+        let bases = match thal::iupac_bases(*w) {
+            Some(bases) if bases.len() > 1 => bases,
+            _ => continue,
+        };
+        *w = match policy {
+            GenomeAmbiguity::ResolveBest if bases.contains(&m) => m,
+            GenomeAmbiguity::ResolveBest => bases[0],
+            _ => *b"ACG".iter().find(|&&b| b != m).unwrap(),
+        };
+    }
+}
+
+/// G/C bases among the `n` bases of a + strand `window` that pair with the
+/// primer's 3' end: the window's last bases for a forward motif, its first
+/// for a reverse one, whose primer binds the - strand 3' end first.
+pub fn clamp_gc_count(window: &[u8], n: usize, strand: Strand) -> usize {
+    match strand {
+        Strand::Forward => thal::gc_clamp_count(window, n),
+        Strand::Reverse => window[..n.min(window.len())].iter().filter(|&&b| matches!(b, b'G' | b'C')).count(),
+    }
+}
+
+/// Score motif `motif_idx` against an uppercased genome `window` of its
+/// length under `opts`: the window with its IUPAC codes resolved against
+/// the motif, and the model's result. Err is the outcome of a window the
+/// motif cannot be scored on (no G/C clamp, a base the native models
+/// reject, or a `WindowDuplex` window whose G/C count alone puts it above
+/// the threshold). Every
+/// scored or pruned window counts in the funnel, and in its survey when
+/// kept; a survey needs every ΔG, so it turns pruning off.
+fn score_window(
+    patterns: &Patterns,
+    motif_idx: usize,
+    window: &[u8],
+    opts: &ScanOptions,
+    funnel: &Funnel,
+) -> Result<(Vec<u8>, thal::ThalResults), SeedOutcome> {
+    let motif = &patterns.motifs[motif_idx];
+    let args = &patterns.args[motif_idx];
+    let mut vicinity = window.to_vec();
+    resolve_ambiguity(&mut vicinity, motif, opts.genome_ambiguity);
+    if opts.gc_clamp.is_some_and(|n| clamp_gc_count(&vicinity, n, Strand::of_motif(motif_idx)) == 0) {
+        return Err(SeedOutcome::NoGcClamp);
+    }
+    if opts.model == ScoreModel::WindowDuplex && funnel.survey.is_none() && thal::validate_bases(&vicinity).is_ok() {
+        let gc = vicinity.iter().filter(|&&b| matches!(b, b'G' | b'C')).count();
+        if !patterns.bounds[motif_idx].can_pass(gc, opts.threshold * thal::CAL_PER_KCAL) {
+            Funnel::add(&funnel.full_length, 1);
+            return Err(SeedOutcome::Pruned);
+        }
+    }
+    let result = match opts.model {
+        ScoreModel::Thal => thal::thal(motif, &vicinity, args, ThalMode::Fast),
+        ScoreModel::WindowDuplex => thal::calculate_thermo(&vicinity, args),
+        ScoreModel::PrimerOnWindow => thal::calculate_thermo_against(motif, &vicinity, args),
+    };
+    // calculate_thermo rejects a window with an IUPAC code
+    if opts.model != ScoreModel::Thal && !result.msg.is_empty() {
+        return Err(SeedOutcome::Ambiguous);
+    }
+    Funnel::add(&funnel.full_length, 1);
+    if let Some(survey) = &funnel.survey {
+        survey.add(result.dg / thal::CAL_PER_KCAL);
+    }
+    Ok((vicinity, result))
+}
+
+/// Scan the `[start, end)` intervals of `seq` in parallel chunks and return
+/// the passing windows sorted by `(pos, motif_idx)`. Positions are relative
+/// to `seq`; a window is reported once even where chunks overlap, and
+/// every window is seen by the chunk it starts in regardless of
+/// `opts.chunk_size`. Once `opts.cancel` is set the remaining chunks are
+/// skipped and the windows found so far returned.
+pub fn scan_intervals(
+    seq_id: &str,
+    seq: &[u8],
+    intervals: &[(usize, usize)],
+    patterns: &Patterns,
+    opts: &ScanOptions,
+    funnel: &Funnel,
+) -> Vec<Hit> {
+    let stride = opts.chunk_size.saturating_sub(opts.chunk_overlap).max(1);
+    // A window starting anywhere in a chunk's stride must end inside it
+    let overlap = opts.chunk_overlap.max(patterns.max_motif_len().saturating_sub(1));
+    let chunk_len = stride + overlap;
+    let should_stop = || {
+        opts.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+            || opts.stop_after.is_some_and(|max| funnel.passing.load(Ordering::Relaxed) >= max)
+    };
+    let mut hits = Vec::new();
+    for &(region_start, region_end) in intervals {
+        let full_seq = &seq[region_start..region_end];
+
+        let region_hits: Vec<Hit> = (0..full_seq.len()).into_par_iter().step_by(stride).flat_map_iter(|start| {
+            if should_stop() {
+                return Vec::new();
+            }
+            let end = (start + chunk_len).min(full_seq.len());
+            let chunk = &full_seq[start..end];
+
+            let mut evaluated = HashSet::new();
+            let mut chunk_hits = Vec::new();
+            let is_last_chunk = end == full_seq.len();
+            let mut seed_hits = 0;
+            let mut chunk_trace = funnel.trace.as_ref().map(|_| Vec::new());
+
+            for mat in patterns.ac.find_overlapping_iter(chunk) {
+                if should_stop() {
+                    break;
+                }
+                let motifs = &patterns.seed_to_motifs[mat.pattern().as_usize()];
+                let hit_pos = mat.start();
+                if is_last_chunk || hit_pos < stride {
+                    seed_hits += 1;
+                    if let Some(counts) = &funnel.seed_counts {
+                        for &(motif_idx, _) in motifs {
+                            Funnel::add(&counts[motif_idx], 1);
+                        }
+                    }
+                }
+                if opts.seeds_only {
+                    continue;
+                }
+
+                for &(motif_idx, offset) in motifs {
+                    let motif = &patterns.motifs[motif_idx];
+                    let genome_start = hit_pos as isize - offset as isize;
+                    let genome_end = genome_start + motif.len() as isize;
+
+                    let outcome = 'extend: {
+                        if genome_start < 0 {
+                            break 'extend SeedOutcome::ClippedStart;
+                        }
+                        if genome_end > chunk.len() as isize {
+                            break 'extend SeedOutcome::ClippedEnd;
+                        }
+                        if !is_last_chunk && genome_start >= stride as isize {
+                            break 'extend SeedOutcome::OtherChunk;
+                        }
+                        // With best_strand one window is scored for both motifs of a primer
+                        let key = if opts.best_strand { motif_idx & !1 } else { motif_idx };
+                        if !evaluated.insert((key, genome_start)) {
+                            break 'extend SeedOutcome::Duplicate;
+                        }
+                        let window = chunk[genome_start as usize..genome_end as usize].to_ascii_uppercase();
+                        if thal::ambiguous_fraction(&window) > opts.max_ambiguous {
+                            break 'extend SeedOutcome::Ambiguous;
+                        }
+                        // The native models have no NN parameters for a gap
+                        if opts.model != ScoreModel::Thal && window.iter().any(|&b| thal::is_gap(b)) {
+                            break 'extend SeedOutcome::Ambiguous;
+                        }
+                        let score = |idx: usize| score_window(patterns, idx, &window, opts, funnel);
+                        let (motif_idx, (vicinity, result), opposite_dg) = if opts.best_strand {
+                            let (fwd, rev) = (motif_idx & !1, motif_idx | 1);
+                            match (score(fwd), score(rev)) {
+                                (Ok(f), Ok(r)) if r.1.dg < f.1.dg => (rev, r, Some(f.1.dg / thal::CAL_PER_KCAL)),
+                                (Ok(f), Ok(r)) => (fwd, f, Some(r.1.dg / thal::CAL_PER_KCAL)),
+                                (Ok(f), Err(_)) => (fwd, f, None),
+                                (Err(_), Ok(r)) => (rev, r, None),
+                                (Err(outcome), Err(_)) => break 'extend outcome,
+                            }
+                        } else {
+                            match score(motif_idx) {
+                                Ok(scored) => (motif_idx, scored, None),
+                                Err(outcome) => break 'extend outcome,
+                            }
+                        };
+                        let motif = &patterns.motifs[motif_idx];
+                        let args = &patterns.args[motif_idx];
+
+                        // ΔG is in cal/mol, convert to kcal/mol for threshold comparison
+                        let dg_kcal = result.dg / thal::CAL_PER_KCAL;
+                        if !(opts.min_dg..=opts.threshold).contains(&dg_kcal) {
+                            break 'extend SeedOutcome::FailedDg;
+                        }
+                        Funnel::add(&funnel.passing, 1);
+                        chunk_hits.push(Hit {
+                            seq_id: seq_id.to_string(),
+                            pos: region_start + start + genome_start as usize,
+                            strand: Strand::of_motif(motif_idx),
+                            dg: dg_kcal,
+                            tm: result.temp,
+                            dh: result.dh / thal::CAL_PER_KCAL,
+                            ds: result.ds,
+                            motif: String::from_utf8_lossy(motif).into_owned(),
+                            gc: opts.gc.then(|| thal::gc_fraction(&vicinity)),
+                            motif_idx,
+                            opposite_dg,
+                            nn_tm: opts.nn_tm.then(|| {
+                                thal::calculate_thermo_against(motif, &vicinity, args).temp
+                            }),
+                            window_dg: opts.compare_dg.then(|| {
+                                thal::calculate_thermo(&vicinity, args).dg / thal::CAL_PER_KCAL
+                            }),
+                            primer_dg: opts.compare_dg.then(|| {
+                                thal::calculate_thermo_against(motif, &vicinity, args).dg / thal::CAL_PER_KCAL
+                            }),
+                            chunk: start / stride,
+                            chunk_offset: genome_start as usize,
+                        });
+                        SeedOutcome::Passed
+                    };
+                    if let Some(trace) = chunk_trace.as_mut() {
+                        trace.push(SeedTrace {
+                            seq_id: seq_id.to_string(),
+                            seed_pos: region_start + start + hit_pos,
+                            motif_idx,
+                            offset,
+                            chunk: start / stride,
+                            outcome,
+                        });
+                    }
+                }
+            }
+            Funnel::add(&funnel.seed_hits, seed_hits);
+            if let (Some(trace), Some(chunk_trace)) = (&funnel.trace, chunk_trace) {
+                trace.lock().unwrap().extend(chunk_trace);
+            }
+            chunk_hits
+        }).collect();
+        hits.extend(region_hits);
+    }
+    hits.sort_unstable_by_key(|hit| (hit.pos, hit.motif_idx));
+    hits
+}
+
+/// Every passing window of a whole record, in position order.
+pub fn scan_record(seq_id: &str, seq: &[u8], patterns: &Patterns, opts: &ScanOptions) -> impl Iterator<Item = Hit> {
+    scan_intervals(seq_id, seq, &[(0, seq.len())], patterns, opts, &Funnel::default()).into_iter()
+}
+
+/// Greedy left-to-right collapse of tandem hits: walking hits in position
+/// order, drop any hit that starts within `min_gap` bases of the last hit
+/// kept for the same motif. Each motif index is already strand-specific.
+pub fn collapse_min_gap(hits: Vec<Hit>, min_gap: usize) -> Vec<Hit> {
+    if min_gap == 0 {
+        return hits;
+    }
+    let mut last_kept: HashMap<usize, usize> = HashMap::new();
+    hits.into_iter()
+        .filter(|hit| match last_kept.get(&hit.motif_idx) {
+            Some(&last) if hit.pos < last + min_gap => false,
+            _ => {
+                last_kept.insert(hit.motif_idx, hit.pos);
+                true
+            }
+        })
+        .collect()
+}
+
+/// Fold each pair of forward and reverse hits of one primer at the same
+/// position (as a palindromic site produces) into a single hit: the more
+/// stable of the two, with the other's ΔG in `opposite_dg`. With
+/// `canonical`, hits pair whenever their motifs share a
+/// `thal::canonical_kmer`, so a primer and its reverse complement loaded
+/// as separate patterns collapse too. Expects the position order of
+/// `scan_intervals`.
+pub fn merge_strands(hits: Vec<Hit>, canonical: bool) -> Vec<Hit> {
+    let same_site = |a: &Hit, b: &Hit| {
+        if canonical {
+            thal::canonical_kmer(a.motif.as_bytes()) == thal::canonical_kmer(b.motif.as_bytes())
+        } else {
+            a.motif_idx / 2 == b.motif_idx / 2
+        }
+    };
+    let mut merged: Vec<Hit> = Vec::with_capacity(hits.len());
+    // Start of the hits at the current position in `merged`
+    let mut pos_start = 0;
+    for hit in hits {
+        if merged.last().is_some_and(|last| last.pos != hit.pos) {
+            pos_start = merged.len();
+        }
+        let partner = merged[pos_start..].iter_mut().find(|m| {
+            m.strand != hit.strand && m.opposite_dg.is_none() && same_site(m, &hit)
+        });
+        match partner {
+            Some(last) => {
+                if hit.dg < last.dg {
+                    let other_dg = last.dg;
+                    *last = hit;
+                    last.opposite_dg = Some(other_dg);
+                } else {
+                    last.opposite_dg = Some(hit.dg);
+                }
+            }
+            None => merged.push(hit),
+        }
+    }
+    merged
+}
+
+/// Most stable ΔG (kcal/mol) among `hits` at each position, in position
+/// order; the per-base signal of a mispriming map. Expects hits sorted by
+/// position, as `scan_intervals` returns them.
+pub fn min_dg_by_position(hits: &[Hit]) -> Vec<(usize, f64)> {
+    let mut track: Vec<(usize, f64)> = Vec::new();
+    for hit in hits {
+        match track.last_mut() {
+            Some((pos, dg)) if *pos == hit.pos => *dg = dg.min(hit.dg),
+            _ => track.push((hit.pos, hit.dg)),
+        }
+    }
+    track
+}
+
+/// A predicted PCR product: a forward-primer site on the + strand and a
+/// downstream reverse-primer site (its reverse complement on the + strand).
+#[derive(Debug, Clone)]
+pub struct Amplicon {
+    /// First base of the forward site.
+    pub start: usize,
+    /// One past the last base of the reverse site.
+    pub end: usize,
+    pub forward: Hit,
+    pub reverse: Hit,
+}
+
+impl Amplicon {
+    /// Product length in bp, primers included.
+    pub fn size(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// Pair the sites of primer `forward` and primer `reverse` (indices in
+/// `Patterns` input order) from one record's `hits` into amplicons of at
+/// most `max_product` bp: the forward primer as given on the + strand and
+/// the reverse primer's reverse complement downstream of it, the two sites
+/// not overlapping. Sites matching repeatedly pair combinatorially, so at
+/// most `max_count` amplicons are returned; the second value is true when
+/// that cap cut the list short. Expects hits in position order, as
+/// `scan_intervals` returns them; amplicons come out ordered by start,
+/// then end.
+pub fn amplicons(
+    hits: &[Hit],
+    forward: usize,
+    reverse: usize,
+    max_product: usize,
+    max_count: usize,
+) -> (Vec<Amplicon>, bool) {
+    let forward_sites = hits.iter().filter(|h| h.motif_idx == 2 * forward && h.strand == Strand::Forward);
+    let reverse_sites: Vec<&Hit> = hits
+        .iter()
+        .filter(|h| h.motif_idx == 2 * reverse + 1 && h.strand == Strand::Reverse)
+        .collect();
+    let mut products = Vec::new();
+    for f in forward_sites {
+        let forward_end = f.pos + f.motif.len();
+        let first = reverse_sites.partition_point(|r| r.pos < forward_end);
+        for r in &reverse_sites[first..] {
+            let end = r.pos + r.motif.len();
+            if end - f.pos > max_product {
+                break;
+            }
+            if products.len() == max_count {
+                return (products, true);
+            }
+            products.push(Amplicon { start: f.pos, end, forward: f.clone(), reverse: (*r).clone() });
+        }
+    }
+    (products, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The primer3 tables `ScoreModel::Thal` scores with.
+    fn load_parameters() {
+        thal::ensure_parameters_loaded("primer3/src/primer3_config/").unwrap();
+    }
+
+    /// A 10 bp site on record "s" with placeholder thermodynamics.
+    fn hit(pos: usize, motif_idx: usize, dg: f64) -> Hit {
+        Hit {
+            seq_id: "s".to_string(),
+            pos,
+            strand: Strand::of_motif(motif_idx),
+            dg,
+            tm: 50.0,
+            dh: -100.0,
+            ds: -300.0,
+            motif: "ACGTACGTAC".to_string(),
+            gc: None,
+            nn_tm: None,
+            window_dg: None,
+            primer_dg: None,
+            opposite_dg: None,
+            motif_idx,
+            chunk: 0,
+            chunk_offset: pos,
+        }
+    }
+
+    #[test]
+    fn test_scan_record_finds_both_strands() {
+        load_parameters();
+        let primer = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let patterns = Patterns::new([(primer.clone(), thal::create_default_args())]);
+        let mut genome = b"TTTTTTTTTT".to_vec();
+        genome.extend(&primer);
+        genome.extend(b"AAAAAAAAAA");
+        genome.extend(thal::reverse_complement(&primer));
+        let opts = ScanOptions { threshold: f64::INFINITY, ..ScanOptions::default() };
+        let hits: Vec<Hit> = scan_record("chr", &genome, &patterns, &opts).collect();
+        assert!(hits.iter().any(|h| h.pos == 10 && h.strand == Strand::Forward && h.motif.as_bytes() == primer));
+        assert!(hits.iter().any(|h| h.pos == 40 && h.strand == Strand::Reverse));
+        assert!(hits.windows(2).all(|w| (w[0].pos, w[0].motif_idx) <= (w[1].pos, w[1].motif_idx)));
+    }
+
+    #[test]
+    fn test_cancel_skips_remaining_chunks() {
+        static CANCEL: AtomicBool = AtomicBool::new(true);
+        let primer = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let patterns = Patterns::new([(primer.clone(), thal::create_default_args())]);
+        let genome = [b"T".repeat(10), primer, b"T".repeat(10)].concat();
+        let opts = ScanOptions { threshold: f64::INFINITY, cancel: Some(&CANCEL), ..ScanOptions::default() };
+        assert_eq!(scan_record("s", &genome, &patterns, &opts).count(), 0);
+    }
+
+    #[test]
+    fn test_gc_clamp_checks_reverse_hits_at_window_start() {
+        load_parameters();
+        let opts = ScanOptions { threshold: f64::INFINITY, gc_clamp: Some(5), ..ScanOptions::default() };
+        let reverse_site = |primer: &[u8]| {
+            let patterns = Patterns::new([(primer.to_vec(), thal::create_default_args())]);
+            let genome = [b"T".repeat(10), thal::reverse_complement(primer), b"T".repeat(10)].concat();
+            scan_record("s", &genome, &patterns, &opts).any(|h| h.pos == 10 && h.strand == Strand::Reverse)
+        };
+        // AT-rich 3' end, though its site ends in GCGC on the + strand
+        assert!(!reverse_site(b"GCGCGATCGATCGATTATAT"));
+        assert!(reverse_site(b"TATATGATCGATCGAGCGCG"));
+        assert_eq!(clamp_gc_count(b"ATATAATCGATCGATCGCGC", 5, Strand::Reverse), 0);
+        assert_eq!(clamp_gc_count(b"ATATAATCGATCGATCGCGC", 5, Strand::Forward), 5);
+    }
+
+    #[test]
+    fn test_motif_longer_than_chunk_spanning_boundary() {
+        load_parameters();
+        let primer = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let patterns = Patterns::new([(primer.clone(), thal::create_default_args())]);
+        // The site at 10..30 crosses the chunk starts at 16 and 24
+        let genome = [b"T".repeat(10), primer, b"T".repeat(10)].concat();
+        let wide = ScanOptions { threshold: f64::INFINITY, ..ScanOptions::default() };
+        let tiny = ScanOptions { chunk_size: 8, chunk_overlap: 0, ..wide.clone() };
+        let expected: Vec<(usize, usize)> = scan_record("s", &genome, &patterns, &wide).map(|h| (h.pos, h.motif_idx)).collect();
+        let found: Vec<(usize, usize)> = scan_record("s", &genome, &patterns, &tiny).map(|h| (h.pos, h.motif_idx)).collect();
+        assert!(expected.contains(&(10, 0)));
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_native_models_score_window_or_primer() {
+        let primer = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let args = thal::create_default_args();
+        let patterns = Patterns::new([(primer.clone(), args.clone())]);
+        // One mismatch at the 3' end of the site
+        let mut site = primer.clone();
+        site[19] = b'A';
+        let genome = [b"T".repeat(10), site.clone(), b"T".repeat(10)].concat();
+        let dg_at_10 = |model| {
+            let opts = ScanOptions { threshold: f64::INFINITY, model, ..ScanOptions::default() };
+            scan_record("s", &genome, &patterns, &opts).find(|h| (h.pos, h.motif_idx) == (10, 0)).unwrap().dg
+        };
+        let window = thal::calculate_thermo(&site, &args).dg / thal::CAL_PER_KCAL;
+        let primer_dg = thal::calculate_thermo_against(&primer, &site, &args).dg / thal::CAL_PER_KCAL;
+        assert_eq!(dg_at_10(ScoreModel::WindowDuplex), window);
+        assert_eq!(dg_at_10(ScoreModel::PrimerOnWindow), primer_dg);
+        assert!(primer_dg > window);
+        // A gap is never scored by the native models
+        let gapped = [b"T".repeat(10), primer[..10].to_vec(), b"-".to_vec(), primer[11..].to_vec()].concat();
+        for model in [ScoreModel::WindowDuplex, ScoreModel::PrimerOnWindow] {
+            let opts = ScanOptions { threshold: f64::INFINITY, max_ambiguous: 1.0, model, ..ScanOptions::default() };
+            assert_eq!(scan_record("s", &gapped, &patterns, &opts).count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_gc_bound_prunes_only_failing_windows() {
+        let primer = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let patterns = Patterns::new([(primer.clone(), thal::create_default_args())]);
+        // Primer seeds in flanks running from AT-only to GC-only
+        let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut genome = Vec::new();
+        for i in 0..40 {
+            for _ in 0..20 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let gc = (state % 40) < i;
+                genome.push(match (gc, state >> 63) {
+                    (true, 0) => b'G',
+                    (true, _) => b'C',
+                    (false, 0) => b'A',
+                    (false, _) => b'T',
+                });
+            }
+            genome.extend(&primer[(i as usize % 14)..][..7]);
+        }
+        let opts = ScanOptions { threshold: -22.0, model: ScoreModel::WindowDuplex, ..ScanOptions::default() };
+        let scan = |funnel: &Funnel| {
+            let hits = scan_intervals("s", &genome, &[(0, genome.len())], &patterns, &opts, funnel);
+            let outcomes: HashMap<_, _> =
+                funnel.take_trace().into_iter().map(|t| ((t.seed_pos, t.motif_idx, t.offset), t.outcome)).collect();
+            (hits.iter().map(|h| (h.pos, h.motif_idx, h.dg)).collect::<Vec<_>>(), outcomes)
+        };
+        let (hits, pruned) = scan(&Funnel::with_trace());
+        // A survey needs every window's ΔG, so nothing is pruned
+        let (expected, scored) = scan(&Funnel { survey: Some(DgHistogram::new()), ..Funnel::with_trace() });
+        assert_eq!(hits, expected);
+        assert!(!hits.is_empty());
+        assert_eq!(pruned.len(), scored.len());
+        let mut n_pruned = 0;
+        for (key, outcome) in &pruned {
+            if *outcome == SeedOutcome::Pruned {
+                n_pruned += 1;
+                assert_eq!(scored[key], SeedOutcome::FailedDg);
+            } else {
+                assert_eq!(scored[key], *outcome);
+            }
+        }
+        assert!(n_pruned > 0);
+    }
+
+    #[test]
+    fn test_amplicons_pair_forward_and_downstream_reverse() {
+        load_parameters();
+        let forward = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let reverse = b"TTGCCGATAGCTCAGGACTA".to_vec();
+        let args = thal::create_default_args();
+        let patterns = Patterns::new([(forward.clone(), args.clone()), (reverse.clone(), args)]);
+        let genome = [
+            b"A".repeat(30),
+            forward,
+            b"A".repeat(100),
+            thal::reverse_complement(&reverse),
+            b"A".repeat(30),
+        ]
+        .concat();
+        let opts = ScanOptions { threshold: f64::INFINITY, ..ScanOptions::default() };
+        let hits: Vec<Hit> = scan_record("s", &genome, &patterns, &opts).collect();
+        let (products, capped) = amplicons(&hits, 0, 1, 1000, usize::MAX);
+        assert!(!capped);
+        assert!(products.iter().any(|a| (a.start, a.end, a.size()) == (30, 170, 140)));
+        assert!(products.iter().all(|a| a.size() <= 1000 && a.forward.pos + 20 <= a.reverse.pos));
+        assert!(!amplicons(&hits, 0, 1, 139, usize::MAX).0.iter().any(|a| a.start == 30 && a.end == 170));
+        // Swapped roles put the "reverse" site upstream
+        assert!(amplicons(&hits, 1, 0, 1000, usize::MAX).0.iter().all(|a| a.start != 30 || a.end != 170));
+        let (one, capped) = amplicons(&hits, 0, 1, 1000, 1);
+        assert_eq!(one.len(), 1);
+        assert_eq!(capped, products.len() > 1);
+    }
+
+    #[test]
+    fn test_best_strand_scores_both_motifs_on_each_window() {
+        load_parameters();
+        let primer = b"GTCAGCTAGGCATCGAATCG".to_vec();
+        let args = thal::create_default_args();
+        let patterns = Patterns::new([(primer.clone(), args.clone())]);
+        let genome = [b"T".repeat(10), primer.clone(), b"T".repeat(10)].concat();
+        let opts = ScanOptions { threshold: f64::INFINITY, best_strand: true, ..ScanOptions::default() };
+        let hits: Vec<Hit> = scan_record("s", &genome, &patterns, &opts).filter(|h| h.pos == 10).collect();
+        assert_eq!(hits.len(), 1);
+        let window = &genome[10..30];
+        let dg = |idx: usize| thal::thal(patterns.motif(idx), window, &args, ThalMode::Fast).dg / thal::CAL_PER_KCAL;
+        let (fwd, rev) = (dg(0), dg(1));
+        assert_eq!((hits[0].dg, hits[0].opposite_dg), (fwd.min(rev), Some(fwd.max(rev))));
+        assert_eq!(hits[0].strand, if rev < fwd { Strand::Reverse } else { Strand::Forward });
+    }
+
+    #[test]
+    fn test_amplicons_skip_overlapping_sites() {
+        // Reverse sites at 5 (overlapping the forward site at 0..10) and 10
+        let hits = vec![hit(0, 0, -15.0), hit(5, 3, -15.0), hit(10, 3, -15.0)];
+        let (products, _) = amplicons(&hits, 0, 1, 100, usize::MAX);
+        assert_eq!(products.iter().map(|a| (a.start, a.end)).collect::<Vec<_>>(), [(0, 20)]);
+    }
+
+    #[test]
+    fn test_dg_histogram_quantiles() {
+        let histogram = DgHistogram::new();
+        assert_eq!(histogram.quantile(0.5), None);
+        for i in 0..100 {
+            histogram.add(-20.0 + 0.1 * i as f64);
+        }
+        histogram.add(-500.0);
+        histogram.add(f64::NAN);
+        assert_eq!(histogram.count(), 101);
+        let median = histogram.quantile(0.5).unwrap();
+        assert!((median - -15.1).abs() <= DgHistogram::BIN_WIDTH + 1e-9, "{}", median);
+        assert!((histogram.quantile(0.0).unwrap() - (DgHistogram::MIN + DgHistogram::BIN_WIDTH)).abs() < 1e-9);
+        assert!(histogram.quantile(1.0).unwrap() >= -10.1);
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        load_parameters();
+        let seq = [b"A".repeat(50), b"ATCGATCGATCGATCGATCG".to_vec(), b"A".repeat(50)].concat();
+        let patterns = Patterns::new([(b"ATCGATCGATCGATCGATCG".to_vec(), thal::create_default_args())]);
+        let opts = ScanOptions { threshold: f64::INFINITY, gc: true, ..ScanOptions::default() };
+        let hits: Vec<Hit> = scan_record("chr1", &seq, &patterns, &opts).collect();
+        assert!(!hits.is_empty());
+        let mut stream = Vec::new();
+        for hit in &hits {
+            write_msgpack_hit(&mut stream, hit).unwrap();
+        }
+        let decoded: Vec<Hit> = read_msgpack_hits(stream.as_slice()).collect::<io::Result<_>>().unwrap();
+        assert_eq!(decoded.len(), hits.len());
+        for (d, h) in decoded.iter().zip(&hits) {
+            assert_eq!(d.to_string(), h.to_string());
+            assert_eq!((d.strand, d.gc, d.nn_tm), (h.strand, h.gc, None));
+        }
+        assert!(read_msgpack_hits(&stream[..stream.len() - 1]).last().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_merge_strands_keeps_more_stable() {
+        // pos 5 has both strands of primer 0; pos 9 has one strand each of
+        // two different primers
+        let hits = vec![hit(5, 0, -11.0), hit(5, 1, -12.0), hit(9, 1, -11.0), hit(9, 2, -13.0)];
+        let merged = merge_strands(hits.clone(), false);
+        assert_eq!(merged.len(), 3);
+        assert_eq!((merged[0].strand, merged[0].dg, merged[0].opposite_dg), (Strand::Reverse, -12.0, Some(-11.0)));
+        assert!(merged[1..].iter().all(|h| h.opposite_dg.is_none()));
+        // The shared (palindromic) motif pairs the two primers at pos 9
+        let merged = merge_strands(hits, true);
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[1].motif_idx, merged[1].opposite_dg), (2, Some(-11.0)));
+    }
+
+    #[test]
+    fn test_hit_display_and_serialize_agree() {
+        let hit = Hit {
+            seq_id: "chr1".to_string(),
+            tm: 55.0,
+            dh: -150.0,
+            ds: -400.25,
+            motif: "ACGT".to_string(),
+            gc: Some(0.5),
+            ..hit(5, 1, -12.3456)
+        };
+        assert_eq!(hit.to_string(), "chr1\t5\t-12.35\t55.00\t-150.00\t-400.25\tACGT");
+        assert!(format!("{:.1}", hit).starts_with("chr1\t5\t-12.3\t"));
+        assert_eq!(format!("{} {:#}", hit.strand, Strand::Forward), "- FWD");
+
+        let mut w = csv::Writer::from_writer(Vec::new());
+        w.serialize(&hit).unwrap();
+        let text = String::from_utf8(w.into_inner().unwrap()).unwrap();
+        assert_eq!(text, "seq_id,pos,strand,dg,tm,dh,ds,motif,gc\nchr1,5,-,-12.3456,55.0,-150.0,-400.25,ACGT,0.5\n");
+    }
+}
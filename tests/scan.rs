@@ -0,0 +1,976 @@
+//! End-to-end runs of the scanning binary over the tiny genome in tests/data.
+
+use rust_search::thal::{self, ThalArgs};
+use std::process::{Command, Output};
+
+const FULLSALT: &str = env!("CARGO_BIN_EXE_main_fullsalt");
+const SIMPLESALT: &str = env!("CARGO_BIN_EXE_main_simplesalt");
+/// The tiny genome scanned for primer.fa's primer.
+const TINY_SCAN: [&str; 4] = ["--file", "tests/data/tiny.fna", "--patterns", "tests/data/primer.fa"];
+
+/// Run `bin` with `args` to completion, whatever its exit status.
+fn run_bin(bin: &str, args: &[&str]) -> Output {
+    Command::new(bin).args(args).output().unwrap_or_else(|e| panic!("failed to run {}: {}", bin, e))
+}
+
+/// `stdout` split into tab-separated fields per line.
+fn parse_rows(stdout: &[u8]) -> Vec<Vec<String>> {
+    String::from_utf8_lossy(stdout).lines().map(|l| l.split('\t').map(str::to_string).collect()).collect()
+}
+
+/// Run main_fullsalt on `genome` with tests/data/primer.fa and return stdout
+/// split into tab-separated fields per line.
+fn run_scan(genome: &str, extra: &[&str]) -> Vec<Vec<String>> {
+    let output = run_bin(FULLSALT, &[&["--file", genome, "--patterns", "tests/data/primer.fa"], extra].concat());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    parse_rows(&output.stdout)
+}
+
+/// main_fullsalt's default conditions, for checking its columns against
+/// the library.
+fn default_args() -> ThalArgs {
+    ThalArgs { dv: 1.5, dntp: 0.6, ..thal::create_default_args() }
+}
+
+#[test]
+fn regions_restrict_hits_to_interval() {
+    let all = run_scan("tests/data/tiny.fna", &[]);
+    assert!(all.iter().any(|h| h[0] == "chr1" && h[1] == "300"));
+    assert!(all.iter().any(|h| h[0] == "chr2"));
+
+    let hits = run_scan("tests/data/tiny.fna", &["--regions", "tests/data/one_interval.bed"]);
+    assert!(hits.iter().any(|h| h[1] == "50"));
+    for h in &hits {
+        let pos: usize = h[1].parse().unwrap();
+        assert_eq!(h[0], "chr1");
+        assert!(pos < 150, "hit at {} outside BED interval", pos);
+    }
+}
+
+#[test]
+fn region_reads_locus_through_fasta_index() {
+    let full = run_scan("tests/data/tiny.fna", &[]);
+    // chr1:41-120 is bases [40, 120); hits must lie wholly inside it
+    let inside: Vec<_> = full
+        .iter()
+        .filter(|h| h[0] == "chr1")
+        .filter(|h| (40..=100).contains(&h[1].parse::<usize>().unwrap()))
+        .cloned()
+        .collect();
+    assert!(!inside.is_empty());
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--region", "chr1:41-120"]), inside);
+
+    let output = run_bin(FULLSALT, &[&TINY_SCAN[..], &["--region", "chr9:1-10"]].concat());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("chr9 is not in the FASTA index"));
+}
+
+#[test]
+fn circular_finds_site_across_origin() {
+    // plasmid is 220 bp with the primer site split 10/10 across the origin
+    let linear = run_scan("tests/data/circular.fna", &[]);
+    assert!(!linear.iter().any(|h| h[1] == "210"));
+
+    let hits = run_scan("tests/data/circular.fna", &["--circular"]);
+    assert!(hits.iter().any(|h| h[1] == "210"));
+    assert!(hits.iter().all(|h| h[1].parse::<usize>().unwrap() < 220));
+}
+
+#[test]
+fn csv_format_has_header_and_same_hits() {
+    let tsv = run_scan("tests/data/tiny.fna", &[]);
+    let output = run_bin(FULLSALT, &[&TINY_SCAN[..], &["--format", "csv"]].concat());
+    let text = String::from_utf8(output.stdout).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("seq_id,pos,dg,tm,dh,ds,motif"));
+    let rows: Vec<Vec<String>> = lines
+        .map(|l| l.split(',').map(str::to_string).collect())
+        .collect();
+    assert_eq!(rows, tsv);
+}
+
+#[test]
+fn output_template_writes_one_file_per_record() {
+    let dir = std::env::temp_dir().join(format!("rust_search_template_{}", std::process::id()));
+    let template = dir.join("nested/{seqid}.tsv");
+    let stdout = run_scan("tests/data/tiny.fna", &["--output-template", template.to_str().unwrap()]);
+    assert!(stdout.is_empty());
+
+    for seq_id in ["chr1", "chr2"] {
+        let text = std::fs::read_to_string(dir.join(format!("nested/{}.tsv", seq_id))).unwrap();
+        assert!(!text.is_empty());
+        assert!(text.lines().all(|l| l.starts_with(&format!("{}\t", seq_id))));
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+const FWD: &str = "ATCGATCGATCGATCGATCG";
+const REV: &str = "CGATCGATCGATCGATCGAT";
+
+/// Every window of tiny.fna holding a 7-mer of primer.fa's primer (FWD) or
+/// its reverse complement (REV) at the same offset as in the motif. The
+/// sites are ATCG repeats, so such windows fall every 2 bp around each
+/// copy: chr1 50 and 300 and chr2 100 on the + strand, chr3 40 on the -.
+const TINY_WINDOWS: [(&str, usize, &str); 56] = [
+    ("chr1", 38, FWD), ("chr1", 40, REV), ("chr1", 42, FWD), ("chr1", 44, REV),
+    ("chr1", 46, FWD), ("chr1", 48, REV), ("chr1", 50, FWD), ("chr1", 52, REV),
+    ("chr1", 54, FWD), ("chr1", 56, REV), ("chr1", 58, FWD), ("chr1", 60, REV),
+    ("chr1", 62, FWD), ("chr1", 64, REV), ("chr1", 288, FWD), ("chr1", 290, REV),
+    ("chr1", 292, FWD), ("chr1", 294, REV), ("chr1", 296, FWD), ("chr1", 298, REV),
+    ("chr1", 300, FWD), ("chr1", 302, REV), ("chr1", 304, FWD), ("chr1", 306, REV),
+    ("chr1", 308, FWD), ("chr1", 310, REV), ("chr1", 312, FWD), ("chr1", 314, REV),
+    ("chr2", 88, FWD), ("chr2", 90, REV), ("chr2", 92, FWD), ("chr2", 94, REV),
+    ("chr2", 96, FWD), ("chr2", 98, REV), ("chr2", 100, FWD), ("chr2", 102, REV),
+    ("chr2", 104, FWD), ("chr2", 106, REV), ("chr2", 108, FWD), ("chr2", 110, REV),
+    ("chr2", 112, FWD), ("chr2", 114, REV), ("chr3", 26, FWD), ("chr3", 28, REV),
+    ("chr3", 30, FWD), ("chr3", 32, REV), ("chr3", 34, FWD), ("chr3", 36, REV),
+    ("chr3", 38, FWD), ("chr3", 40, REV), ("chr3", 42, FWD), ("chr3", 44, REV),
+    ("chr3", 46, FWD), ("chr3", 48, REV), ("chr3", 50, FWD), ("chr3", 52, REV),
+];
+
+#[test]
+fn scan_reports_every_seeded_window() {
+    // No dG limit: every seeded window is reported, whatever its score
+    let hits = run_scan("tests/data/tiny.fna", &["--threshold", "100"]);
+    let found: Vec<(&str, usize, &str)> = hits.iter().map(|h| (h[0].as_str(), h[1].parse().unwrap(), h[6].as_str())).collect();
+    assert_eq!(found, TINY_WINDOWS);
+
+    let passing = run_scan("tests/data/tiny.fna", &[]);
+    for site in [("chr1", 50, FWD), ("chr1", 300, FWD), ("chr2", 100, FWD), ("chr3", 40, REV)] {
+        assert!(passing.iter().any(|h| (h[0].as_str(), h[1].parse().unwrap(), h[6].as_str()) == site), "{:?}", site);
+    }
+    for h in &passing {
+        assert!(TINY_WINDOWS.contains(&(h[0].as_str(), h[1].parse().unwrap(), h[6].as_str())), "{:?}", h);
+        assert!(h[2].parse::<f64>().unwrap() <= -10.0, "{:?}", h);
+    }
+
+    // The same scan under the native model, whose perfect-site values are
+    // primer3 oligotm's (50 mM Na+, 1.5 mM Mg2+, 0.6 mM dNTP, 200 nM):
+    // dG -21.10 kcal/mol, Tm 59.25 C, dH -160.40 kcal/mol, dS -449.15 cal/K/mol
+    let output = run_bin(SIMPLESALT, &TINY_SCAN);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let native = parse_rows(&output.stdout);
+    for (seq_id, pos, motif) in [("chr1", "50", FWD), ("chr1", "300", FWD), ("chr2", "100", FWD), ("chr3", "40", REV)] {
+        let site = native.iter().find(|h| h[0] == seq_id && h[1] == pos).unwrap();
+        assert_eq!(site[2..], ["-21.10", "59.25", "-160.40", "-449.15", motif], "{}:{}", seq_id, pos);
+    }
+}
+
+#[test]
+fn simplesalt_excess_dntp_gives_finite_values() {
+    let output = run_bin(SIMPLESALT, &[&TINY_SCAN[..], &["--threshold", "0", "--mg", "0.5", "--dntp", "0.6"]].concat());
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(!text.is_empty());
+    assert!(!text.to_lowercase().contains("nan"), "{}", text);
+}
+
+#[test]
+fn simplesalt_score_primer_penalizes_mismatched_windows() {
+    let run = |extra: &[&str]| {
+        let output = run_bin(SIMPLESALT, &[&TINY_SCAN[..], &["--threshold", "100"], extra].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let mut lines = parse_rows(&output.stdout);
+        lines.sort();
+        lines
+    };
+    let window = run(&[]);
+    let primer = run(&["--score-primer"]);
+    assert_eq!(window.len(), primer.len());
+    let mut mismatched = 0;
+    for (w, p) in window.iter().zip(&primer) {
+        assert_eq!(w[..2], p[..2]);
+        assert_eq!(p.len(), 8);
+        let (window_dg, primer_dg): (f64, f64) = (w[2].parse().unwrap(), p[2].parse().unwrap());
+        if p[6] == p[7] {
+            // Both modes use the library model, so a perfect match scores the same
+            assert_eq!(w[2..6], p[2..6]);
+        } else {
+            mismatched += 1;
+            // A terminal mismatch only shortens the duplex, so the margin is small
+            assert!(primer_dg > window_dg, "{:?} vs {:?}", w, p);
+        }
+    }
+    assert!(mismatched > 0);
+}
+
+#[test]
+fn simplesalt_finds_long_motif_across_chunk_boundary() {
+    // A 160 bp primer starting 120 bp before the default chunk's 1 Mb end:
+    // longer than the default 100 bp overlap, so it fits in no chunk unless
+    // the overlap is sized from the motif
+    let primer = "GTCAGCTAGGCATCGAATCG".repeat(8);
+    let site = 1_000_000 - 120;
+    let genome = format!(">long\n{}{}{}\n", "A".repeat(site), primer, "A".repeat(200));
+    let dir = std::env::temp_dir();
+    let genome_path = dir.join(format!("long_genome_{}.fa", std::process::id()));
+    let primer_path = dir.join(format!("long_primer_{}.fa", std::process::id()));
+    std::fs::write(&genome_path, genome).unwrap();
+    std::fs::write(&primer_path, format!(">long\n{}\n", primer)).unwrap();
+    let output = run_bin(SIMPLESALT, &["--file", genome_path.to_str().unwrap(), "--patterns", primer_path.to_str().unwrap()]);
+    std::fs::remove_file(&genome_path).unwrap();
+    std::fs::remove_file(&primer_path).unwrap();
+    assert!(output.status.success());
+    let rows = parse_rows(&output.stdout);
+    assert_eq!(rows.iter().filter(|r| r[1] == site.to_string()).count(), 1, "{:?}", rows);
+}
+
+#[test]
+fn min_dg_keeps_only_the_band() {
+    let all = run_scan("tests/data/tiny.fna", &[]);
+    let mut dgs: Vec<f64> = all.iter().map(|h| h[2].parse().unwrap()).collect();
+    dgs.sort_by(f64::total_cmp);
+    dgs.dedup();
+    // cut halfway between two reported values so rounding can't straddle it
+    let mid = dgs.len() / 2;
+    let cut = (dgs[mid - 1] + dgs[mid]) / 2.0;
+
+    let band = run_scan("tests/data/tiny.fna", &[&format!("--min-dg={}", cut)]);
+    let expected: Vec<_> = all.iter().filter(|h| h[2].parse::<f64>().unwrap() >= cut).collect();
+    assert!(band.len() < all.len());
+    assert_eq!(band.iter().collect::<Vec<_>>(), expected);
+}
+
+#[test]
+fn rc_window_column_is_reverse_complement_of_window() {
+    let hits = run_scan("tests/data/tiny.fna", &["--rc-window"]);
+    let site = hits.iter().find(|h| h[0] == "chr1" && h[1] == "50").unwrap();
+    assert_eq!(site[7], "CGATCGATCGATCGATCGAT");
+}
+
+#[test]
+fn params_subcommand_prints_all_stacks_without_inputs() {
+    let output = run_bin(FULLSALT, &["--na=20", "params"]);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("Na+ (mM)\t20.00"));
+    let stacks = text.lines().filter(|l| l.as_bytes().get(2) == Some(&b'/')).count();
+    assert_eq!(stacks, 10);
+}
+
+#[test]
+fn condition_flags_fall_back_to_environment() {
+    let params = |args: &[&str]| {
+        let output = Command::new(FULLSALT)
+            .env("RUST_SEARCH_NA", "20")
+            .env("RUST_SEARCH_TEMP", "60")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let from_env = params(&["params"]);
+    assert!(from_env.contains("Na+ (mM)\t20.00"));
+    assert!(from_env.contains("Temperature (C)\t60.00"));
+    // The flag wins over the variable
+    assert!(params(&["--na=80", "params"]).contains("Na+ (mM)\t80.00"));
+}
+
+#[test]
+fn count_only_tallies_hits_per_record_motif_and_strand() {
+    let hits = run_scan("tests/data/tiny.fna", &[]);
+    let counts = run_scan("tests/data/tiny.fna", &["--count-only"]);
+    assert!(!counts.is_empty());
+    for row in &counts {
+        let motif = if row[2] == "+" { "ATCGATCGATCGATCGATCG" } else { "CGATCGATCGATCGATCGAT" };
+        let expected = hits.iter().filter(|h| h[0] == row[0] && h[6] == motif).count();
+        assert_eq!(row[1], "ATCGATCGATCGATCGATCG");
+        assert_eq!(row[3].parse::<usize>().unwrap(), expected);
+    }
+    let total: usize = counts.iter().map(|r| r[3].parse::<usize>().unwrap()).sum();
+    assert_eq!(total, hits.len());
+}
+
+#[test]
+fn context_column_flanks_window_and_clamps_at_record_ends() {
+    let hits = run_scan("tests/data/tiny.fna", &["--context", "5"]);
+    let site = hits.iter().find(|h| h[0] == "chr1" && h[1] == "50").unwrap();
+    assert_eq!(site[7].len(), 30);
+    assert_eq!(&site[7][5..25], "ATCGATCGATCGATCGATCG");
+    assert!(site[7][..5].bytes().all(|b| b.is_ascii_lowercase()));
+
+    let hits = run_scan("tests/data/tiny.fna", &["--context", "1000"]);
+    let site = hits.iter().find(|h| h[0] == "chr1" && h[1] == "50").unwrap();
+    assert_eq!(site[7].find(char::is_uppercase), Some(50));
+}
+
+#[test]
+fn align_view_writes_a_block_per_hit() {
+    let path = std::env::temp_dir().join(format!("align_view_{}.txt", std::process::id()));
+    let hits = run_scan("tests/data/tiny.fna", &["--align-view", path.to_str().unwrap()]);
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4 * hits.len());
+    let block = lines.chunks(4).find(|b| b[0].starts_with("# chr1\t50\t")).unwrap();
+    assert_eq!(block[1], "ATCGATCGATCGATCGATCG");
+    assert_eq!(block[2], "|".repeat(20));
+    assert_eq!(block[3], "ATCGATCGATCGATCGATCG");
+}
+
+#[test]
+fn boulder_format_emits_one_record_per_hit() {
+    let tsv = run_scan("tests/data/tiny.fna", &[]);
+    let output = run_bin(FULLSALT, &[&TINY_SCAN[..], &["--format", "boulder"]].concat());
+    let text = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<&str> = text.split_terminator("=\n").collect();
+    assert_eq!(records.len(), tsv.len());
+    let first: Vec<&str> = records[0].lines().collect();
+    assert_eq!(first[0], format!("SEQUENCE_ID={}", tsv[0][0]));
+    assert_eq!(first[2], format!("PRIMER_DG={}", tsv[0][2]));
+    assert_eq!(first[3], format!("PRIMER_TM={}", tsv[0][3]));
+    assert_eq!(first[6], format!("PRIMER_SEQUENCE={}", tsv[0][6]));
+}
+
+#[test]
+fn dimer_subcommand_accepts_sequences_and_fasta_files() {
+    let dimer = |a: &str, b: &str| {
+        let output = run_bin(FULLSALT, &["dimer", a, b]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        parse_rows(&output.stdout)
+    };
+    let from_seqs = dimer("ATCGATCGATCGATCGATCG", "cgatcgatcgatcgatcgat");
+    let keys: Vec<&str> = from_seqs.iter().map(|r| r[0].as_str()).collect();
+    assert_eq!(keys, ["dg", "dh", "ds", "tm", "align_end_1", "align_end_2"]);
+    assert_eq!(dimer("tests/data/primer.fa", "CGATCGATCGATCGATCGAT"), from_seqs);
+}
+
+#[test]
+fn fold_subcommand_prints_mfe_structure() {
+    let output = run_bin(FULLSALT, &["fold", "ccggccggttttccggccgg"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let fields = parse_rows(&output.stdout);
+    assert_eq!(fields[0][0], "dg");
+    assert!(fields[0][1].parse::<f64>().unwrap() < 0.0);
+    assert_eq!(fields[1], ["structure", "((((((((....))))))))"]);
+}
+
+#[test]
+fn rna_as_dna_scores_u_as_t() {
+    for bin in [FULLSALT, SIMPLESALT] {
+        let run = |patterns: &str, extra: &[&str]| {
+            let scan = ["--file", "tests/data/tiny.fna", "--patterns", patterns, "--pattern-format", "txt"];
+            let output = run_bin(bin, &[&scan[..], extra].concat());
+            assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+            let mut lines = parse_rows(&output.stdout);
+            lines.sort();
+            (lines, String::from_utf8(output.stderr).unwrap())
+        };
+        let (dna, _) = run("tests/data/primers.txt", &[]);
+        let (rna, warning) = run("tests/data/rna_primer.txt", &["--rna-as-dna"]);
+        assert!(!dna.is_empty());
+        assert_eq!(rna, dna);
+        assert!(warning.contains("DNA nearest-neighbor parameters"));
+    }
+}
+
+#[test]
+fn dimer_subcommand_rejects_gapped_oligo() {
+    let output = run_bin(FULLSALT, &["dimer", "ATCGAT-CGATCG", "CGATCGATCGAT"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("gap at position 6"));
+}
+
+#[test]
+fn precision_controls_decimal_places() {
+    let default = run_scan("tests/data/tiny.fna", &[]);
+    let precise = run_scan("tests/data/tiny.fna", &["--precision", "4"]);
+    assert_eq!(precise.len(), default.len());
+    for (p, d) in precise.iter().zip(&default) {
+        assert_eq!(p[2].split('.').nth(1).unwrap().len(), 4);
+        let (p, d): (f64, f64) = (p[2].parse().unwrap(), d[2].parse().unwrap());
+        assert!((p - d).abs() <= 0.0051, "{} vs {}", p, d);
+    }
+}
+
+#[test]
+fn malformed_record_fails_cleanly_unless_skipped() {
+    let scan = ["--file", "tests/data/messy.fq", "--patterns", "tests/data/primer.fa"];
+    let run = |extra: &[&str]| run_bin(FULLSALT, &[&scan[..], extra].concat());
+    let strict = run(&[]);
+    let stderr = String::from_utf8_lossy(&strict.stderr);
+    assert!(!strict.status.success());
+    assert!(stderr.contains("broken") && !stderr.contains("panicked"), "{}", stderr);
+
+    let lenient = run(&["--skip-errors"]);
+    assert!(lenient.status.success());
+    assert!(String::from_utf8_lossy(&lenient.stderr).contains("Skipping malformed record"));
+    assert!(String::from_utf8(lenient.stdout).unwrap().starts_with("chr1\t"));
+}
+
+#[test]
+fn mmap_reader_gives_same_hits() {
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--mmap"]), run_scan("tests/data/tiny.fna", &[]));
+}
+
+#[test]
+fn inline_pattern_matches_patterns_file() {
+    let output = run_bin(FULLSALT, &["--file", "tests/data/tiny.fna", "--pattern", "ATCGATCGATCGATCGATCG"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(parse_rows(&output.stdout), run_scan("tests/data/tiny.fna", &[]));
+}
+
+#[test]
+fn genome_ambiguity_policies() {
+    let dg_at_site = |extra: &[&str]| {
+        run_scan("tests/data/iupac.fna", extra)
+            .into_iter()
+            .find(|h| h[1] == "30" && h[6] == "ATCGATCGATCGATCGATCG")
+            .map(|h| h[2].parse::<f64>().unwrap())
+    };
+    assert_eq!(dg_at_site(&[]), None);
+
+    let perfect = run_scan("tests/data/tiny.fna", &[])
+        .into_iter()
+        .find(|h| h[0] == "chr1" && h[1] == "50")
+        .map(|h| h[2].parse::<f64>().unwrap());
+    let best = dg_at_site(&["--max-ambiguous", "0.1", "--genome-ambiguity", "resolve-best"]);
+    assert_eq!(best, perfect);
+
+    let mismatch = dg_at_site(&["--max-ambiguous", "0.1", "--genome-ambiguity", "treat-as-mismatch",
+        "--threshold", "0"]);
+    assert!(mismatch.unwrap() > best.unwrap());
+}
+
+#[test]
+fn nn_tm_column_scores_the_aligned_primer() {
+    let args = default_args();
+    let hits = run_scan("tests/data/tiny.fna", &["--nn-tm"]);
+    let site = hits.iter().find(|h| h[0] == "chr1" && h[1] == "50").unwrap();
+    let expected = thal::calculate_thermo(site[6].as_bytes(), &args).temp;
+    assert_eq!(site[7], format!("{:.2}", expected));
+}
+
+#[test]
+fn max_hits_caps_reported_hits() {
+    let all = run_scan("tests/data/tiny.fna", &[]);
+    assert!(all.len() > 3);
+    let capped = run_scan("tests/data/tiny.fna", &["--max-hits", "3"]);
+    assert_eq!(capped.len(), 3);
+    assert!(capped.iter().all(|h| all.contains(h)));
+}
+
+#[test]
+fn normalized_column_divides_dg_by_steps() {
+    for h in run_scan("tests/data/tiny.fna", &["--normalized", "--precision", "6"]) {
+        let (dg, per_bp): (f64, f64) = (h[2].parse().unwrap(), h[7].parse().unwrap());
+        assert!((per_bp - dg / (h[6].len() - 1) as f64).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn selftest_reproduces_santalucia_examples() {
+    let output = run_bin(FULLSALT, &["selftest"]);
+    let text = String::from_utf8(output.stdout).unwrap();
+    let santalucia: Vec<&str> = text.lines().filter(|l| l.contains("SantaLucia")).collect();
+    assert!(!santalucia.is_empty());
+    assert!(santalucia.iter().all(|l| l.starts_with("PASS\t")), "{}", text);
+}
+
+#[test]
+fn debug_chunks_columns_locate_hits() {
+    for h in run_scan("tests/data/tiny.fna", &["--debug-chunks"]) {
+        // tiny records fit in the first chunk, so the offset is the position
+        assert_eq!(h[7], "0");
+        assert_eq!(h[8], h[1]);
+    }
+}
+
+#[test]
+fn per_pattern_conditions_override_globals() {
+    let global = run_scan("tests/data/tiny.fna", &["--threshold", "0"]);
+    let listed = run_scan("tests/data/tiny.fna", &["--threshold", "0", "--conditions", "tests/data/conditions.tsv"]);
+    let via_cli = run_scan("tests/data/tiny.fna", &["--threshold", "0", "--temp", "60"]);
+    assert_eq!(listed, via_cli);
+    assert_ne!(listed, global);
+}
+
+#[test]
+fn delta_tm_subcommand_reports_shift_from_globals() {
+    let delta_tm = |extra: &[&str]| {
+        let output = run_bin(FULLSALT, &[&["--na=20", "delta-tm", "GTCAGCTAGGCATCGAATCG"], extra].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        parse_rows(&output.stdout).last().unwrap()[1].parse::<f64>().unwrap()
+    };
+    // the remaining --vs-* defaults equal the global defaults
+    assert_eq!(delta_tm(&["--vs-na", "20"]), 0.0);
+    assert!(delta_tm(&[]) > 0.0);
+}
+
+#[test]
+fn empty_inputs_are_reported() {
+    let run = |genome: &str, patterns: &str| run_bin(FULLSALT, &["--file", genome, "--patterns", patterns]);
+    let no_patterns = run("tests/data/tiny.fna", "tests/data/empty.fa");
+    assert!(!no_patterns.status.success());
+    assert!(String::from_utf8_lossy(&no_patterns.stderr).contains("no patterns loaded"));
+
+    let no_genome = run("tests/data/empty.fa", "tests/data/primer.fa");
+    assert!(!no_genome.status.success());
+    assert!(String::from_utf8_lossy(&no_genome.stderr).contains("no genome records in tests/data/empty.fa"));
+}
+
+#[test]
+fn merge_strands_folds_palindromic_sites() {
+    // ACGT repeats are their own reverse complement, so each site hits on
+    // both strands at the same position
+    let run = |extra: &[&str]| {
+        let scan = ["--file", "tests/data/palindrome.fna", "--pattern", "ACGTACGTACGTACGTACGT", "--threshold", "0"];
+        let output = run_bin(FULLSALT, &[&scan[..], extra].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        parse_rows(&output.stdout)
+    };
+    let all = run(&[]);
+    let merged = run(&["--merge-strands"]);
+    assert!(!merged.is_empty());
+    assert_eq!(merged.len() * 2, all.len());
+    for row in &merged {
+        assert_eq!(row.len(), 9);
+        assert!(!row[8].is_empty());
+    }
+    // --best-strand scores both motifs on every window itself and reports
+    // each strand's dG
+    let best = run(&["--best-strand"]);
+    assert_eq!(best.len(), merged.len());
+    for (b, m) in best.iter().zip(&merged) {
+        assert_eq!(b.len(), 10);
+        assert_eq!(b[..8], m[..8]);
+        let mut dgs = [&b[8], &b[9]];
+        let mut expected = [&m[2], &m[8]];
+        dgs.sort();
+        expected.sort();
+        assert_eq!(dgs, expected);
+    }
+}
+
+#[test]
+fn bedgraph_reports_min_dg_per_position() {
+    let hits = run_scan("tests/data/tiny.fna", &[]);
+    let track = run_scan("tests/data/tiny.fna", &["--bedgraph"]);
+    assert_eq!(track[0], ["track type=bedGraph name=mispriming"]);
+    for row in &track[1..] {
+        let start: usize = row[1].parse().unwrap();
+        assert_eq!(row[2].parse::<usize>().unwrap(), start + 1);
+        let at_pos: Vec<f64> = hits.iter()
+            .filter(|h| h[0] == row[0] && h[1] == row[1])
+            .map(|h| h[2].parse().unwrap())
+            .collect();
+        assert_eq!(row[3].parse::<f64>().unwrap(), at_pos.iter().cloned().fold(f64::INFINITY, f64::min));
+    }
+    assert!(track.len() > 1);
+}
+
+#[test]
+fn chunk_size_is_validated_and_short_overlap_raised() {
+    let default = run_scan("tests/data/tiny.fna", &[]);
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--chunk-size", "64"]), default);
+
+    // An overlap shorter than the 20 bp primer is raised to fit it
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--chunk-size", "64", "--overlap", "10"]), default);
+    assert!(!run_bin(FULLSALT, &[&TINY_SCAN[..], &["--chunk-size", "20"]].concat()).status.success());
+}
+
+#[test]
+fn trace_seeds_reports_each_extension() {
+    let path = std::env::temp_dir().join(format!("trace_seeds_{}.tsv", std::process::id()));
+    let hits = run_scan("tests/data/tiny.fna", &["--chunk-size", "64", "--trace-seeds", path.to_str().unwrap()]);
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut lines = text.lines();
+    assert_eq!(lines.next(), Some("seq_id\tseed_pos\tmotif\tstrand\toffset\tchunk\toutcome"));
+    let outcomes: Vec<&str> = lines.map(|l| l.rsplit('\t').next().unwrap()).collect();
+    assert_eq!(outcomes.iter().filter(|&&o| o == "passed").count(), hits.len());
+    // 64 bp chunks overlap, so windows in the overlap defer to the next chunk
+    assert!(outcomes.contains(&"other-chunk"));
+}
+
+#[test]
+fn report_no_hits_lists_unmatched_primers() {
+    let output = run_bin(FULLSALT, &[&TINY_SCAN[..], &["--pattern", "GGGGGGGGCCCCCCCCGGGG", "--report-no-hits"]].concat());
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let listed: Vec<&str> = stderr.lines().filter(|l| l.starts_with("no hits\t")).collect();
+    assert_eq!(listed, ["no hits\tpattern_1\tGGGGGGGGCCCCCCCCGGGG"]);
+}
+
+#[test]
+fn group_by_pattern_orders_hits_by_primer() {
+    // TCGA repeats are their own reverse complement and bind the same
+    // ATCG repeats as primer.fa's primer
+    let extra = ["--pattern", "TCGATCGATCGATCGATCGA", "--threshold", "0"];
+    let interleaved = run_scan("tests/data/tiny.fna", &extra);
+    let grouped = run_scan("tests/data/tiny.fna", &[&extra[..], &["--group-by", "pattern"]].concat());
+    let sorted = run_scan("tests/data/tiny.fna", &[&extra[..], &["--group-by", "pattern", "--sort-dg"]].concat());
+    assert_eq!(grouped.len(), interleaved.len());
+
+    // primer.fa's primer first, then the inline one; never back again
+    let primer_of = |row: &Vec<String>| row[6] == "TCGATCGATCGATCGATCGA";
+    let switches = |rows: &[Vec<String>]| rows.windows(2).filter(|w| primer_of(&w[0]) != primer_of(&w[1])).count();
+    assert!(switches(&interleaved) > 1);
+    assert_eq!(switches(&grouped), 1);
+    assert!(!primer_of(&grouped[0]));
+
+    let mut expected = grouped.clone();
+    expected.sort_by(|a, b| primer_of(a).cmp(&primer_of(b))
+        .then(a[2].parse::<f64>().unwrap().total_cmp(&b[2].parse::<f64>().unwrap())));
+    let dgs = |rows: &[Vec<String>]| rows.iter().map(|r| (primer_of(r), r[2].clone())).collect::<Vec<_>>();
+    assert_eq!(dgs(&sorted), dgs(&expected));
+}
+
+/// amplicon.fna scanned for a primer pair whose product spans 30..170.
+const AMPLICON_SCAN: [&str; 11] = [
+    "--file", "tests/data/amplicon.fna", "--threshold", "0",
+    "--pattern", "GTCAGCTAGGCATCGAATCG", "--pattern", "TTGCCGATAGCTCAGGACTA",
+    "--amplicon", "pattern_1", "pattern_2",
+];
+
+#[test]
+fn amplicon_pairs_forward_and_reverse_sites() {
+    let output = run_bin(FULLSALT, &AMPLICON_SCAN);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let rows = parse_rows(&output.stdout);
+    let product = rows.iter().find(|r| r[1] == "30" && r[2] == "170").expect("amplicon 30-170");
+    assert_eq!(product[0], "amp");
+    assert_eq!(product[3], "140");
+    assert_eq!((product[4].as_str(), product[7].as_str()), ("pattern_1", "pattern_2"));
+
+    let short = run_bin(FULLSALT, &[&AMPLICON_SCAN[..], &["--max-product-size", "100"]].concat());
+    // The only product is longer, so none are reported
+    assert_eq!(short.status.code(), Some(1));
+    assert!(short.stdout.is_empty());
+}
+
+#[test]
+fn exit_status_tells_hits_from_no_hits_and_errors() {
+    let status = |args: &[&str]| run_bin(FULLSALT, args).status.code();
+    let scan = TINY_SCAN;
+    assert_eq!(status(&scan), Some(0));
+    assert_eq!(status(&[&scan[..], &["--threshold=-100"]].concat()), Some(1));
+    assert_eq!(status(&["--file", "tests/data/missing.fna", "--patterns", "tests/data/primer.fa"]), Some(2));
+    assert_eq!(status(&["--file", "tests/data/tiny.fna", "--patterns", "tests/data/empty.fa"]), Some(2));
+    assert_eq!(status(&[&scan[..], &["--threshold", "abc"]].concat()), Some(2));
+    assert_eq!(status(&["params"]), Some(0));
+
+    let simple = |args: &[&str]| run_bin(SIMPLESALT, args).status.code();
+    assert_eq!(simple(&scan), Some(0));
+    assert_eq!(simple(&[&scan[..], &["--threshold=-100"]].concat()), Some(1));
+    assert_eq!(simple(&["--file", "tests/data/tiny.fna", "--patterns", "tests/data/missing.fa"]), Some(2));
+}
+
+#[test]
+fn amplicon_reports_tm_diff_and_max_tm_diff_filters() {
+    let all = parse_rows(&run_bin(FULLSALT, &AMPLICON_SCAN).stdout);
+    assert!(!all.is_empty());
+    for row in &all {
+        let (fwd_tm, rev_tm): (f64, f64) = (row[6].parse().unwrap(), row[9].parse().unwrap());
+        assert!((row[10].parse::<f64>().unwrap() - (fwd_tm - rev_tm).abs()).abs() <= 0.011);
+    }
+    let strict = run_bin(FULLSALT, &[&AMPLICON_SCAN[..], &["--max-tm-diff", "0"]].concat());
+    assert!(String::from_utf8_lossy(&strict.stderr).contains("differ in Tm by"));
+    assert!(parse_rows(&strict.stdout).iter().all(|r| r[10] == "0.00"));
+}
+
+#[test]
+fn survey_prints_increasing_percentiles_instead_of_hits() {
+    let rows = run_scan("tests/data/tiny.fna", &["--survey"]);
+    assert_eq!(rows.iter().map(|r| r[0].as_str()).collect::<Vec<_>>(),
+        ["1", "5", "10", "25", "50", "75", "90", "95", "99"]);
+    let dg: Vec<f64> = rows.iter().map(|r| r[1].parse().unwrap()).collect();
+    assert!(dg.windows(2).all(|w| w[0] <= w[1]));
+    // Every hit is a scored window, so the hits' best dG bounds the 1st percentile
+    let best = run_scan("tests/data/tiny.fna", &[]).iter().map(|h| h[2].parse::<f64>().unwrap())
+        .fold(f64::INFINITY, f64::min);
+    assert!(dg[0] >= best - 0.01);
+}
+
+#[test]
+fn seed_stats_counts_seed_matches_per_primer() {
+    let output = run_bin(FULLSALT, &[&TINY_SCAN[..], &["--seed-stats"]].concat());
+    assert!(output.status.success());
+    let rows = parse_rows(&output.stdout);
+    // Seeds are counted but never extended
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("full-length windows: 0"), "{}", stderr);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0][0], "pattern_1");
+    // Each match counts once per offset its seed occurs at in the primer,
+    // so the per-primer total is at least the funnel's seed hits
+    let funnel: usize = stderr.split("seed hits: ").nth(1).unwrap().split('\t').next().unwrap().parse().unwrap();
+    assert!(funnel > 0);
+    assert!(rows[0][1].parse::<usize>().unwrap() >= funnel, "{:?}", rows);
+}
+
+#[test]
+fn compare_dg_columns_agree_on_a_perfect_site() {
+    let hits = run_scan("tests/data/tiny.fna", &["--compare-dg"]);
+    let site = hits.iter().find(|h| h[0] == "chr1" && h[1] == "50").unwrap();
+    assert_eq!(site.len(), 9);
+    assert_eq!(site[7], site[8]);
+    assert!(site[7].parse::<f64>().unwrap() < 0.0);
+}
+
+#[test]
+fn wrapped_soft_masked_fasta_scans_like_clean_fasta_in_every_binary() {
+    // wrapped.fna is tiny.fna wrapped at 37 columns with CRLF line ends,
+    // trailing spaces and a lowercase middle third
+    let sites = |bin: &str, genome: &str| {
+        let output = run_bin(bin, &["--file", genome, "--patterns", "tests/data/primer.fa", "--threshold", "100"]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let mut rows = parse_rows(&output.stdout);
+        rows.sort();
+        rows
+    };
+    let key = |row: &Vec<String>| (row[0].clone(), row[1].clone(), row[6].clone());
+    let (full, simple) = (FULLSALT, SIMPLESALT);
+    let full_hits = sites(full, "tests/data/tiny.fna");
+    let simple_hits = sites(simple, "tests/data/tiny.fna");
+    assert!(!simple_hits.is_empty());
+    assert_eq!(sites(full, "tests/data/wrapped.fna"), full_hits);
+    assert_eq!(sites(simple, "tests/data/wrapped.fna"), simple_hits);
+    // Both binaries seed through search::scan_intervals, so they find the
+    // same sites (their dG models differ)
+    assert_eq!(simple_hits.iter().map(key).collect::<Vec<_>>(), full_hits.iter().map(key).collect::<Vec<_>>());
+}
+
+#[test]
+fn check_subcommand_reports_each_rule() {
+    // Loose dG limits leave the thal-scored rules out of the comparison
+    let output = run_bin(FULLSALT, &["check", "tests/data/check_primers.fa", "--min-hairpin-dg=-1000", "--min-dimer-dg=-1000"]);
+    assert_eq!(output.status.code(), Some(1));
+    let rows = parse_rows(&output.stdout);
+    assert_eq!(rows[0], ["id", "length", "gc", "tm", "homopolymer", "gc_clamp", "hairpin", "self_dimer", "result"]);
+    assert_eq!(rows[1], ["good", "PASS", "PASS", "PASS", "PASS", "PASS", "PASS", "PASS", "PASS"]);
+    assert_eq!(rows[2], ["short_at_run", "FAIL", "FAIL", "FAIL", "FAIL", "PASS", "PASS", "PASS", "FAIL"]);
+    assert_eq!(rows[3], ["palindrome", "PASS", "PASS", "PASS", "PASS", "PASS", "PASS", "PASS", "PASS"]);
+
+    // Under the default limits the palindrome's 8 bp hairpin stem and
+    // full-length self-dimer fail
+    let output = run_bin(FULLSALT, &["check", "tests/data/check_primers.fa"]);
+    assert_eq!(output.status.code(), Some(1));
+    let rows = parse_rows(&output.stdout);
+    let palindrome = rows.iter().find(|r| r[0] == "palindrome").unwrap();
+    assert_eq!(*palindrome, ["palindrome", "PASS", "PASS", "PASS", "PASS", "PASS", "FAIL", "FAIL", "FAIL"]);
+
+    let relaxed = run_bin(FULLSALT, &["check", "tests/data/check_primers.fa", "--min-hairpin-dg=-1000",
+        "--min-dimer-dg=-1000", "--min-len", "10", "--min-gc", "0", "--min-tm=-100", "--max-homopolymer", "5"]);
+    assert!(relaxed.status.success());
+}
+
+#[test]
+fn no_self_complementary_correction_lowers_palindrome_tm() {
+    let tm = |extra: &[&str]| {
+        let output = run_bin(FULLSALT, &[extra, &["delta-tm", "GAATTCGAATTC"]].concat());
+        assert!(output.status.success());
+        parse_rows(&output.stdout)[0][1].parse::<f64>().unwrap()
+    };
+    let naive = ThalArgs { symmetry_correction: false, ..default_args() };
+    let forced = tm(&["--no-self-complementary-correction"]);
+    assert_eq!(format!("{:.2}", forced), format!("{:.2}", thal::calculate_thermo(b"GAATTCGAATTC", &naive).temp));
+    assert!(forced < tm(&[]));
+}
+
+#[test]
+fn score_subcommand_tabulates_each_oligo() {
+    let output = run_bin(FULLSALT, &["score", "tests/data/oligos.fa"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Skipping gapped"));
+    let rows = parse_rows(&output.stdout);
+    assert_eq!(rows[0], ["id", "length", "gc", "dg", "dh", "ds", "tm"]);
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[1][..3], ["p1", "20", "0.50"]);
+    assert_eq!(rows[2][..3], ["gc_rich", "14", "1.00"]);
+    let args = default_args();
+    let expected = thal::calculate_thermo(b"ATCGATCGATCGATCGATCG", &args);
+    assert_eq!(rows[1][3], format!("{:.2}", expected.dg / 1000.0));
+    assert_eq!(rows[1][6], format!("{:.2}", expected.temp));
+}
+
+#[test]
+fn color_always_marks_stable_dg_and_auto_stays_plain_when_piped() {
+    let plain = run_scan("tests/data/tiny.fna", &[]);
+    assert!(plain.iter().flatten().all(|f| !f.contains('\x1b')));
+    // With the threshold 20 kcal/mol above every hit, all are two bands beyond it
+    let colored = run_scan("tests/data/tiny.fna", &["--color", "always", "--threshold", "20"]);
+    let site = colored.iter().find(|h| h[0] == "chr1" && h[1] == "50").unwrap();
+    assert!(site[2].starts_with("\x1b[31m"), "{:?}", site[2]);
+    assert!(site.iter().enumerate().all(|(i, f)| i == 2 || !f.contains('\x1b')));
+}
+
+#[test]
+fn msgpack_format_decodes_to_the_tsv_hits() {
+    let tsv = run_scan("tests/data/tiny.fna", &[]);
+    let output = run_bin(FULLSALT, &[&TINY_SCAN[..], &["--format", "msgpack"]].concat());
+    assert!(output.status.success());
+    let hits: Vec<Vec<String>> = rust_search::search::read_msgpack_hits(output.stdout.as_slice())
+        .map(|hit| hit.unwrap().fields(2))
+        .collect();
+    assert_eq!(hits, tsv);
+}
+
+#[test]
+fn subtract_hairpin_adjusts_dg_by_the_hairpin_penalty() {
+    let plain = run_scan("tests/data/tiny.fna", &[]);
+    let hits = run_scan("tests/data/tiny.fna", &["--subtract-hairpin"]);
+    assert_eq!(hits.len(), plain.len());
+    for (h, p) in hits.iter().zip(&plain) {
+        assert_eq!(h[..7], p[..]);
+        let (dg, hairpin, adjusted): (f64, f64, f64) = (h[2].parse().unwrap(), h[7].parse().unwrap(), h[8].parse().unwrap());
+        assert!(hairpin <= 0.0);
+        assert!((adjusted - (dg - hairpin)).abs() <= 0.011, "{:?}", h);
+    }
+}
+
+#[test]
+fn half_dg_reports_the_primer_halves_on_every_strand() {
+    let hits = run_scan("tests/data/tiny.fna", &["--half-dg"]);
+    assert!(hits.iter().any(|h| h[6] != hits[0][6]));
+    // Halves describe the primer as given, whichever strand it hit
+    for h in &hits {
+        assert_eq!(h[7..], hits[0][7..]);
+    }
+    let (five, three): (f64, f64) = (hits[0][7].parse().unwrap(), hits[0][8].parse().unwrap());
+    assert!(five < 0.0 && three < 0.0);
+}
+
+#[test]
+fn duplicate_patterns_are_merged_with_a_warning() {
+    let run = |extra: &[&str]| {
+        let scan = ["--file", "tests/data/tiny.fna", "--patterns", "tests/data/primers.txt", "--pattern-format", "txt"];
+        let output = run_bin(FULLSALT, &[&scan[..], extra].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+    let (plain, _) = run(&[]);
+    let (same, warning) = run(&["--pattern", "atcgatcgatcgatcgatcg"]);
+    assert_eq!(same, plain);
+    assert!(warning.contains("merging pattern pattern_2 into pattern_1 (identical sequence)"), "{}", warning);
+
+    // A reverse complement is only merged on request
+    let rc = ["--pattern", "CGATCGATCGATCGATCGAT"];
+    assert_ne!(run(&rc).0, plain);
+    let (merged, warning) = run(&[&rc[..], &["--dedup-rc"]].concat());
+    assert_eq!(merged, plain);
+    assert!(warning.contains("merging pattern pattern_2 into pattern_1 (reverse complement)"), "{}", warning);
+}
+
+#[test]
+fn txt_pattern_format_loads_one_sequence_per_line() {
+    let run = |bin: &str, patterns: &str, format: &str| {
+        run_bin(bin, &["--file", "tests/data/tiny.fna", "--patterns", patterns, "--pattern-format", format])
+    };
+    let full = FULLSALT;
+    let from_txt = run(full, "tests/data/primers.txt", "txt");
+    assert!(from_txt.status.success(), "{}", String::from_utf8_lossy(&from_txt.stderr));
+    let fasta = run(full, "tests/data/primer.fa", "fasta");
+    assert_eq!(from_txt.stdout, fasta.stdout);
+    let simple = SIMPLESALT;
+    assert_eq!(run(simple, "tests/data/primers.txt", "txt").stdout, run(simple, "tests/data/primer.fa", "fasta").stdout);
+
+    let misread = run(full, "tests/data/primer.fa", "txt");
+    assert!(!misread.status.success());
+    assert!(String::from_utf8_lossy(&misread.stderr).contains("FASTA header"));
+
+    // txt lines are pattern_1.., and inline patterns are numbered on from them
+    let output = run_bin(full, &["--file", "tests/data/amplicon.fna", "--threshold", "0", "--patterns",
+        "tests/data/primers.txt", "--pattern-format", "txt", "--pattern", "CCCCCGGGGGCCCCCGGGGG", "--report-no-hits"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("no hits\tpattern_1\tATCGATCGATCGATCGATCG"), "{}", stderr);
+    assert!(stderr.contains("no hits\tpattern_2\tCCCCCGGGGGCCCCCGGGGG"), "{}", stderr);
+}
+
+#[test]
+fn summary_json_records_inputs_and_counts() {
+    let path = std::env::temp_dir().join(format!("summary_{}.json", std::process::id()));
+    let hits = run_scan("tests/data/tiny.fna", &["--summary-json", path.to_str().unwrap()]);
+    let summary: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(summary["genome"], "tests/data/tiny.fna");
+    assert_eq!(summary["patterns_file"], "tests/data/primer.fa");
+    assert_eq!(summary["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(summary["parameters"]["threshold"], -10.0);
+    assert!(summary["parameters"]["min_dg"].is_null());
+    assert_eq!(summary["patterns_loaded"], 1);
+    assert_eq!(summary["records"], 3);
+    assert_eq!(summary["hits_reported"], hits.len());
+    assert!(summary["elapsed_seconds"].as_f64().unwrap() >= 0.0);
+}
+
+#[test]
+fn include_and_exclude_seqs_select_records() {
+    let all = run_scan("tests/data/tiny.fna", &[]);
+    let ids = |hits: &[Vec<String>]| {
+        let mut ids: Vec<String> = hits.iter().map(|h| h[0].clone()).collect();
+        ids.dedup();
+        ids
+    };
+    assert!(ids(&all).len() > 1);
+    let only_chr1: Vec<_> = all.iter().filter(|h| h[0] == "chr1").cloned().collect();
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--include-seqs", "chr1"]), only_chr1);
+    let without_chr1: Vec<_> = all.iter().filter(|h| h[0] != "chr1").cloned().collect();
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--exclude-seqs", "chr1"]), without_chr1);
+
+    let list = std::env::temp_dir().join(format!("include_seqs_{}.txt", std::process::id()));
+    std::fs::write(&list, "# wanted\nchr1\n").unwrap();
+    let from_file = run_scan("tests/data/tiny.fna", &["--include-seqs", list.to_str().unwrap()]);
+    std::fs::remove_file(&list).unwrap();
+    assert_eq!(from_file, only_chr1);
+}
+
+#[test]
+fn one_based_shifts_hit_and_amplicon_starts_but_not_bedgraph() {
+    let zero = run_scan("tests/data/tiny.fna", &[]);
+    let one = run_scan("tests/data/tiny.fna", &["--one-based"]);
+    assert_eq!(zero.len(), one.len());
+    for (z, o) in zero.iter().zip(&one) {
+        assert_eq!(o[1].parse::<usize>().unwrap(), z[1].parse::<usize>().unwrap() + 1);
+        assert_eq!((&z[..1], &z[2..]), (&o[..1], &o[2..]));
+    }
+    assert_eq!(run_scan("tests/data/tiny.fna", &["--bedgraph", "--one-based"]), run_scan("tests/data/tiny.fna", &["--bedgraph"]));
+
+    let rows = parse_rows(&run_bin(FULLSALT, &[&AMPLICON_SCAN[..], &["--one-based"]].concat()).stdout);
+    assert!(rows.iter().any(|r| r[..4] == ["amp", "31", "170", "140"]), "{:?}", rows);
+}
+
+#[cfg(unix)]
+#[test]
+fn sigint_flushes_partial_hits_and_exits_130() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::Stdio;
+
+    // The genome streams through stdin, so the scan is mid-genome when the
+    // signal lands: the first record is scanned, the rest still unsent
+    let record = |i: usize| format!(">rec{}\n{}ATCGATCGATCGATCGATCG{}\n", i, "A".repeat(5000), "A".repeat(5000));
+    let mut child = Command::new(FULLSALT)
+        .args(["--file", "/dev/stdin", "--patterns", "tests/data/primer.fa"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    // The parser fills a 64 KiB buffer before handing out the first record,
+    // so send part of the next one too
+    let head = format!("{}>rec1\n{}", record(0), "A".repeat(100_000));
+    stdin.write_all(head.as_bytes()).unwrap();
+    stdin.flush().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut first = String::new();
+    stdout.read_line(&mut first).unwrap();
+    assert!(first.starts_with("rec0\t"), "{}", first);
+
+    assert_eq!(unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) }, 0);
+    // A large remainder of the genome that must go unscanned
+    let rest: String = (2..500).map(record).collect();
+    // The scan may stop reading once interrupted
+    let _ = write!(stdin, "\n{}", rest);
+    drop(stdin);
+
+    let mut rest_out = String::new();
+    std::io::Read::read_to_string(&mut stdout, &mut rest_out).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(130));
+    let lines: Vec<String> = std::iter::once(first).chain(rest_out.lines().map(|l| format!("{}\n", l))).collect();
+    assert!(lines.iter().all(|l| l.starts_with("rec0\t")), "{:?}", lines);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Interrupted: 1 records scanned"), "{}", stderr);
+}
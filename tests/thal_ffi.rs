@@ -0,0 +1,84 @@
+//! Links against the `thal.c` object compiled by build.rs and checks it
+//! against a known primer3-py result, so a broken C build fails `cargo test`.
+
+use rust_search::thal::{
+    create_default_args, ensure_parameters_loaded, hairpin, heterodimer, thal, thal_ffi, ThalAlignmentType, ThalArgs,
+    ThalArgsC, ThalMode, ThalResultsC,
+};
+use std::ffi::CStr;
+
+const CONFIG: &str = "primer3/src/primer3_config/";
+
+// primer3.calc_heterodimer("ATCGATCGATCGATCGATCG", "CGATCGATCGATCGATCGAT")
+// -> ThermoResult(tm=57.29, dg=-21065.89, dh=-160400.00, ds=-449.25)
+const SEQ1: &[u8] = b"ATCGATCGATCGATCGATCG";
+const SEQ2: &[u8] = b"CGATCGATCGATCGATCGAT";
+
+/// primer3-py `calc_heterodimer` defaults.
+fn primer3_py_args() -> ThalArgs {
+    ThalArgs { dv: 1.5, dntp: 0.6, ..create_default_args() }
+}
+
+#[test]
+fn raw_thal_call_returns_sane_tm() {
+    ensure_parameters_loaded(CONFIG).expect("Failed to load params");
+    let args = primer3_py_args();
+    let c_args = ThalArgsC {
+        alignment_type: args.alignment_type,
+        max_loop: args.max_loop,
+        mv: args.mv,
+        dv: args.dv,
+        dntp: args.dntp,
+        dna_conc: args.dna_conc,
+        temp: args.temp.as_kelvin(),
+        dimer: args.dimer,
+    };
+    let s1 = [SEQ1, b"\0"].concat();
+    let s2 = [SEQ2, b"\0"].concat();
+
+    let (msg, tm, dg) = unsafe {
+        let mut o: ThalResultsC = std::mem::zeroed();
+        thal_ffi(s1.as_ptr(), s2.as_ptr(), &c_args, ThalMode::Fast, &mut o);
+        let msg = CStr::from_ptr(o.msg.as_ptr()).to_string_lossy().into_owned();
+        (msg, o.temp, o.dg)
+    };
+
+    assert_eq!(msg, "");
+    assert!(tm.is_finite() && tm > 0.0 && tm < 100.0, "implausible Tm {}", tm);
+    assert!(dg < 0.0);
+}
+
+#[test]
+fn wrapper_matches_primer3_py() {
+    ensure_parameters_loaded(CONFIG).expect("Failed to load params");
+    let result = thal(SEQ1, SEQ2, &primer3_py_args(), ThalMode::Fast);
+
+    assert_eq!(result.msg, "");
+    assert!((result.temp - 57.29).abs() < 0.5, "Tm {} != 57.29", result.temp);
+    assert!((result.dg - -21065.89).abs() < 100.0, "dG {} != -21065.89", result.dg);
+    assert!((result.dh - -160400.0).abs() < 500.0, "dH {} != -160400", result.dh);
+}
+
+#[test]
+fn hairpin_finds_stem_loop() {
+    ensure_parameters_loaded(CONFIG).expect("Failed to load params");
+    // GC stem of 8 around a 4-T loop
+    let stem_loop = hairpin(b"CCGGCCGGTTTTCCGGCCGG", &primer3_py_args());
+    let unfolded = hairpin(b"AAAAAAAAAAAAAAAAAAAA", &primer3_py_args());
+    assert_eq!(stem_loop.msg, "");
+    assert!(stem_loop.dg < unfolded.dg.min(0.0), "hairpin dG {} vs {}", stem_loop.dg, unfolded.dg);
+}
+
+#[test]
+fn heterodimer_reports_alignment_ends_in_each_oligo() {
+    ensure_parameters_loaded(CONFIG).expect("Failed to load params");
+    // Hairpin settings in args are overridden
+    let args = ThalArgs { alignment_type: ThalAlignmentType::Hairpin, dimer: 0, ..primer3_py_args() };
+    let result = heterodimer(SEQ1, SEQ2, &args).unwrap();
+    assert_eq!(result.msg, "");
+    assert!((result.dg - -21065.89).abs() < 100.0, "dG {} != -21065.89", result.dg);
+    assert!(result.sec_struct.is_some());
+    for (end, len) in [(result.align_end_1, SEQ1.len()), (result.align_end_2, SEQ2.len())] {
+        assert!(end >= 1 && end as usize <= len, "align end {} outside 1..={}", end, len);
+    }
+}